@@ -1,25 +1,116 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+    time::Duration,
+};
 
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::{Deserialize, Serialize};
 use text_template::Template;
 use url::Url;
 
 use crate::{
-    app_module::{post_install::process_post_install, sources::process_source, versions},
+    app_module::{
+        platform,
+        post_install::process_post_install,
+        sources::{Cancellation, SourceProgress, process_source},
+        verify::process_verify,
+        versions,
+    },
+    config::CyreneConfig,
     errors::CyreneError,
+    http,
 };
 
 #[derive(Serialize, Deserialize)]
 pub struct CyreneApp {
+    #[serde(default)]
+    pub metadata: AppMetadata,
     pub settings: AppSettings,
     pub versions: AppVersions,
     pub sources: Vec<AppSources>,
     pub binaries: HashMap<String, String>,
+    /// Binaries replacing `binaries` on a specific OS
+    #[serde(default)]
+    pub platform_binaries: HashMap<String, HashMap<String, String>>,
     pub post_install: Option<Vec<AppPostInstallCommands>>,
+    pub verify: Option<AppVerify>,
+    /// Release channels, mapping a name usable in place of a version to a pattern of versions
+    #[serde(default)]
+    pub channels: BTreeMap<String, String>,
+    /// Version, version requirement or channel installed with `name@recommended`
+    pub recommended: Option<String>,
+    #[serde(default)]
+    pub http: AppHttpConfig,
+    /// Extra operations run with `cyrene run`
+    #[serde(default)]
+    pub actions: BTreeMap<String, AppAction>,
 }
 fn default_true() -> bool {
     true
 }
+fn default_verify_args() -> Vec<String> {
+    vec!["--version".to_string()]
+}
+fn default_verify_expect() -> String {
+    "${version}".to_string()
+}
+/// Optional information about an app
+#[derive(Default, Serialize, Deserialize)]
+pub struct AppMetadata {
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub repo: Option<String>,
+    pub min_cyrene_version: Option<String>,
+}
+/// HTTP client settings used when fetching versions and sources
+#[derive(Default, Serialize, Deserialize)]
+pub struct AppHttpConfig {
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Request timeout in seconds
+    pub timeout: Option<u64>,
+}
+impl AppHttpConfig {
+    /// Client with the app's headers and timeout applied on top of the global settings
+    pub fn client(&self, config: &CyreneConfig) -> Result<reqwest::Client, CyreneError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| CyreneError::HttpHeader(name.clone()))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| CyreneError::HttpHeader(name.to_string()))?;
+            headers.insert(name, value);
+        }
+        let mut builder = http::client_builder(config)?.default_headers(headers);
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+        builder.build().map_err(CyreneError::HttpClient)
+    }
+}
+/// Command run after post-install to check that a binary works
+#[derive(Serialize, Deserialize)]
+pub struct AppVerify {
+    /// Binary to run, as named in `binaries`
+    pub binary: String,
+    #[serde(default = "default_verify_args")]
+    pub args: Vec<String>,
+    /// Text the output must contain
+    #[serde(default = "default_verify_expect")]
+    pub expect: String,
+}
+/// Extra operation of an app, run with `cyrene run`
+#[derive(Serialize, Deserialize)]
+pub struct AppAction {
+    /// Binary to run, as named in `binaries`
+    pub binary: String,
+    /// Arguments passed before the ones given on the command line
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub description: Option<String>,
+}
 #[derive(Serialize, Deserialize)]
 pub struct AppSettings {
     pub upgrade_latest: bool,
@@ -37,6 +128,9 @@ pub enum AppVersions {
         url: Url,
         command: Vec<AppVersionsUrlCommand>,
     },
+    Npm {
+        package: String,
+    },
 }
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -55,27 +149,163 @@ pub enum AppVersionsUrlCommand {
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum AppSources {
-    TarXz { url: String },
-    TarGz { url: String },
-    Zip { url: String },
-    File { url: String },
+    TarXz {
+        url: String,
+        #[serde(default)]
+        strip_components: usize,
+        #[serde(default)]
+        members: Vec<String>,
+    },
+    TarGz {
+        url: String,
+        #[serde(default)]
+        strip_components: usize,
+        #[serde(default)]
+        members: Vec<String>,
+    },
+    TarZst {
+        url: String,
+        #[serde(default)]
+        strip_components: usize,
+        #[serde(default)]
+        members: Vec<String>,
+    },
+    TarBz2 {
+        url: String,
+        #[serde(default)]
+        strip_components: usize,
+        #[serde(default)]
+        members: Vec<String>,
+    },
+    Zip {
+        url: String,
+        #[serde(default)]
+        strip_components: usize,
+        #[serde(default)]
+        members: Vec<String>,
+    },
+    File {
+        url: String,
+        /// Expected SHA-256 digest of the download
+        sha256: Option<String>,
+    },
+    /// A gzip-compressed executable, named after `path` or the download without `.gz`
+    GzExec {
+        url: String,
+        path: Option<String>,
+    },
+    /// An xz-compressed executable, named after `path` or the download without `.xz`
+    XzExec {
+        url: String,
+        path: Option<String>,
+    },
+}
+
+impl AppSources {
+    /// Source type, as written in the app
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppSources::TarXz { .. } => "tar_xz",
+            AppSources::TarGz { .. } => "tar_gz",
+            AppSources::TarZst { .. } => "tar_zst",
+            AppSources::TarBz2 { .. } => "tar_bz2",
+            AppSources::Zip { .. } => "zip",
+            AppSources::File { .. } => "file",
+            AppSources::GzExec { .. } => "gz_exec",
+            AppSources::XzExec { .. } => "xz_exec",
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        match self {
+            AppSources::TarXz { url, .. }
+            | AppSources::TarGz { url, .. }
+            | AppSources::TarZst { url, .. }
+            | AppSources::TarBz2 { url, .. }
+            | AppSources::Zip { url, .. }
+            | AppSources::File { url, .. }
+            | AppSources::GzExec { url, .. }
+            | AppSources::XzExec { url, .. } => url,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum AppPostInstallCommands {
-    SetExec { path: String },
+    SetExec {
+        path: String,
+    },
+    /// Verify a file against its entry in a `sha256sum`-style checksums file
+    VerifySha256 {
+        path: String,
+        checksums_url: String,
+    },
+    /// Verify a file against a detached signature made by an ASCII-armored public key
+    VerifyGpg {
+        path: String,
+        signature_url: String,
+        public_key: String,
+    },
+}
+
+/// A step of installing an app, as explained without running it
+pub struct AppPlanStep {
+    pub step: String,
+    pub detail: String,
+    /// Whether the step needs network access
+    pub network: bool,
+}
+impl AppPlanStep {
+    pub fn new(step: &str, detail: String, network: bool) -> Self {
+        Self {
+            step: step.to_string(),
+            detail,
+            network,
+        }
+    }
 }
 
 // Instance functions
 impl CyreneApp {
-    pub async fn get_versions(&self) -> Result<Vec<String>, CyreneError> {
-        versions::process_version(&self.versions).await
+    pub async fn get_versions(&self, config: &CyreneConfig) -> Result<Vec<String>, CyreneError> {
+        let client = self.http.client(config)?;
+        versions::process_version(&self.versions, config.max_response_size(), &client).await
     }
 
-    pub async fn install(&self, version: &str, installation_dir: &Path) -> Result<(), CyreneError> {
+    /// Install sources into `installation_dir`.
+    ///
+    /// The destination is passed down to every source explicitly, so installs never depend on the
+    /// process working directory and can safely run concurrently. If any source fails, the
+    /// directory is removed so no partial install is left behind.
+    pub async fn install(
+        &self,
+        version: &str,
+        installation_dir: &Path,
+        config: &CyreneConfig,
+        cancel: &Cancellation,
+    ) -> Result<(), CyreneError> {
+        let client = self.http.client(config)?;
+        let mut progress = SourceProgress::new(self.sources.len());
         for source in &self.sources {
-            process_source(source, version, installation_dir).await?;
+            progress.advance();
+            if let Err(e) = process_source(
+                source,
+                version,
+                installation_dir,
+                &progress,
+                &client,
+                cancel,
+            )
+            .await
+            {
+                if installation_dir.exists() {
+                    fs::remove_dir_all(installation_dir).map_err(|e| {
+                        CyreneError::InstallRollback(installation_dir.to_path_buf(), e)
+                    })?;
+                }
+                return Err(e);
+            }
         }
 
         Ok(())
@@ -85,23 +315,143 @@ impl CyreneApp {
         &self,
         version: &str,
         installation_dir: &Path,
+        config: &CyreneConfig,
     ) -> Result<(), CyreneError> {
         let mut values = HashMap::new();
         values.insert("version", version);
         if let Some(post_install) = &self.post_install {
+            let client = self.http.client(config)?;
             for post_install in post_install {
-                process_post_install(post_install, version, installation_dir).await?;
+                process_post_install(post_install, version, installation_dir, &client).await?;
             }
         }
 
         Ok(())
     }
 
+    /// Run the verify command, if the app declares one
+    pub fn verify(&self, version: &str, installation_dir: &Path) -> Result<(), CyreneError> {
+        let Some(verify) = &self.verify else {
+            return Ok(());
+        };
+        let binaries = self.binaries(version)?;
+        let bin_path = binaries
+            .get(&verify.binary)
+            .ok_or(CyreneError::VerifyBinaryNotFound(verify.binary.clone()))?;
+        let mut binary = installation_dir.to_path_buf();
+        binary.push(bin_path);
+
+        process_verify(verify, version, &binary)
+    }
+
+    /// Steps taken to fetch versions, download sources, run post-install steps and verify
+    pub fn plan(&self, version: &str) -> Vec<AppPlanStep> {
+        let values = platform::template_values(version);
+        let fill = |f: &str| Template::from(f).fill_in(&values).to_string();
+        let mut steps = Vec::new();
+
+        let versions = match &self.versions {
+            AppVersions::Github { repo, .. } => format!("GitHub releases of {}", repo),
+            AppVersions::Url { url, .. } => url.to_string(),
+            AppVersions::Npm { package } => format!("npm package {}", package),
+        };
+        steps.push(AppPlanStep::new("Fetch versions", versions, true));
+        for source in &self.sources {
+            steps.push(AppPlanStep::new(
+                &format!("Download {}", source.kind()),
+                fill(source.url()),
+                true,
+            ));
+            if let AppSources::File {
+                sha256: Some(sha256),
+                ..
+            } = source
+            {
+                steps.push(AppPlanStep::new(
+                    "Verify checksum",
+                    format!("{} against {}", fill(source.url()), sha256),
+                    false,
+                ));
+            }
+        }
+        for post_install in self.post_install.iter().flatten() {
+            steps.push(match post_install {
+                AppPostInstallCommands::SetExec { path } => {
+                    AppPlanStep::new("Set executable", fill(path), false)
+                }
+                AppPostInstallCommands::VerifySha256 {
+                    path,
+                    checksums_url,
+                } => AppPlanStep::new(
+                    "Verify checksum",
+                    format!("{} against {}", fill(path), fill(checksums_url)),
+                    true,
+                ),
+                AppPostInstallCommands::VerifyGpg {
+                    path,
+                    signature_url,
+                    ..
+                } => AppPlanStep::new(
+                    "Verify signature",
+                    format!("{} against {}", fill(path), fill(signature_url)),
+                    true,
+                ),
+            });
+        }
+        if let Some(verify) = &self.verify {
+            let mut command = vec![verify.binary.clone()];
+            command.extend(verify.args.iter().map(|f| fill(f)));
+            steps.push(AppPlanStep::new(
+                "Verify",
+                format!("{}, expecting {}", command.join(" "), fill(&verify.expect)),
+                false,
+            ));
+        }
+
+        steps
+    }
+
+    /// Binary path, relative to the installation, and arguments of an action
+    pub fn action(
+        &self,
+        action: &str,
+        version: &str,
+    ) -> Result<(String, Vec<String>), CyreneError> {
+        if self.actions.is_empty() {
+            return Err(CyreneError::AppNoActions);
+        }
+        let Some(app_action) = self.actions.get(action) else {
+            let actions: Vec<&str> = self.actions.keys().map(|f| f.as_str()).collect();
+            return Err(CyreneError::AppActionNotFound(
+                action.to_string(),
+                actions.join(", "),
+            ));
+        };
+        let bin_path = self.binaries(version)?.remove(&app_action.binary).ok_or(
+            CyreneError::ActionBinaryNotFound(app_action.binary.clone(), action.to_string()),
+        )?;
+        let values = platform::template_values(version);
+        let args = app_action
+            .args
+            .iter()
+            .map(|f| Template::from(f.as_str()).fill_in(&values).to_string())
+            .collect();
+
+        Ok((bin_path, args))
+    }
+
     pub fn binaries(&self, version: &str) -> Result<HashMap<String, String>, CyreneError> {
-        let mut values = HashMap::new();
-        values.insert("version", version);
-        let new_map = self
-            .binaries
+        self.binaries_for_os(version, platform::os())
+    }
+
+    fn binaries_for_os(
+        &self,
+        version: &str,
+        os: &str,
+    ) -> Result<HashMap<String, String>, CyreneError> {
+        let values = platform::template_values(version);
+        let binaries = self.platform_binaries.get(os).unwrap_or(&self.binaries);
+        let new_map = binaries
             .clone()
             .into_iter()
             .map(|(key, value)| {
@@ -194,4 +544,285 @@ node = "node-v${version}-linux-x64/bin/node"
         assert_eq!(app.sources.len(), 1);
         assert_eq!(app.binaries.len(), 1);
     }
+
+    #[test]
+    fn platform_binaries_app() {
+        let config = r#"
+[settings]
+upgrade_latest = false
+
+[versions]
+type = "github"
+repo = "nodejs/node"
+
+[[sources]]
+type = "tar_xz"
+url = "https://nodejs.org/dist/v${version}/node-v${version}-linux-x64.tar.xz"
+
+[binaries]
+node = "node-v${version}-linux-x64/bin/node"
+npm = "node-v${version}-linux-x64/bin/npm"
+
+[platform_binaries.windows]
+"node.exe" = "node-v${version}-win-x64/node.exe"
+"npm.cmd" = "node-v${version}-win-x64/npm.cmd"
+"#;
+        let app = CyreneApp::from_str(config).unwrap();
+
+        let linux = app.binaries_for_os("22.0.0", "linux").unwrap();
+        assert_eq!(linux.len(), 2);
+        assert_eq!(
+            linux.get("node").unwrap(),
+            "node-v22.0.0-linux-x64/bin/node"
+        );
+
+        let windows = app.binaries_for_os("22.0.0", "windows").unwrap();
+        assert_eq!(windows.len(), 2);
+        assert!(!windows.contains_key("node"));
+        assert_eq!(
+            windows.get("npm.cmd").unwrap(),
+            "node-v22.0.0-win-x64/npm.cmd"
+        );
+    }
+
+    #[test]
+    fn metadata_app() {
+        let config = r#"
+[metadata]
+description = "Manage locally installed binaries"
+homepage = "https://github.com/Damillora/cyrene"
+
+[settings]
+upgrade_latest = false
+
+[versions]
+type = "github"
+repo = "Damillora/cyrene"
+
+[[sources]]
+type = "tar_xz"
+url = "https://github.com/Damillora/cyrene/releases/download/${env.version}/cyrene-x86_64-unknown-linux-gnu.tar.xz"
+
+[binaries]
+cyrene = "cyrene-x86_64-unknown-linux-gnu/cyrene"
+"#;
+        let app = CyreneApp::from_str(config).unwrap();
+
+        assert_eq!(
+            app.metadata.description.as_deref(),
+            Some("Manage locally installed binaries")
+        );
+        assert!(app.metadata.repo.is_none());
+    }
+
+    #[test]
+    fn http_app() {
+        let config = r#"
+[settings]
+upgrade_latest = false
+
+[versions]
+type = "github"
+repo = "Damillora/cyrene"
+
+[[sources]]
+type = "tar_xz"
+url = "https://github.com/Damillora/cyrene/releases/download/${env.version}/cyrene-x86_64-unknown-linux-gnu.tar.xz"
+
+[binaries]
+cyrene = "cyrene-x86_64-unknown-linux-gnu/cyrene"
+
+[http]
+timeout = 30
+
+[http.headers]
+Authorization = "Bearer token"
+"#;
+        let app = CyreneApp::from_str(config).unwrap();
+
+        assert_eq!(app.http.timeout, Some(30));
+        assert!(app.http.client(&CyreneConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn http_invalid_header() {
+        let http = AppHttpConfig {
+            headers: BTreeMap::from([("Bad Header".to_string(), "value".to_string())]),
+            timeout: None,
+        };
+        assert!(matches!(
+            http.client(&CyreneConfig::default()),
+            Err(CyreneError::HttpHeader(_))
+        ));
+    }
+
+    #[test]
+    fn plan_app() {
+        let config = r#"
+[settings]
+upgrade_latest = false
+
+[versions]
+type = "npm"
+package = "typescript"
+
+[[sources]]
+type = "tar_gz"
+url = "https://registry.npmjs.org/typescript/-/typescript-${version}.tgz"
+
+[binaries]
+tsc = "package/bin/tsc"
+
+[[post_install]]
+type = "set_exec"
+path = "package/bin/tsc"
+
+[verify]
+binary = "tsc"
+"#;
+        let app = CyreneApp::from_str(config).unwrap();
+        let steps: Vec<(String, String, bool)> = app
+            .plan("5.4.0")
+            .into_iter()
+            .map(|f| (f.step, f.detail, f.network))
+            .collect();
+
+        assert_eq!(
+            steps,
+            vec![
+                (
+                    "Fetch versions".to_string(),
+                    "npm package typescript".to_string(),
+                    true
+                ),
+                (
+                    "Download tar_gz".to_string(),
+                    "https://registry.npmjs.org/typescript/-/typescript-5.4.0.tgz".to_string(),
+                    true
+                ),
+                (
+                    "Set executable".to_string(),
+                    "package/bin/tsc".to_string(),
+                    false
+                ),
+                (
+                    "Verify".to_string(),
+                    "tsc --version, expecting 5.4.0".to_string(),
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn actions_app() {
+        let config = r#"
+[settings]
+upgrade_latest = false
+
+[versions]
+type = "github"
+repo = "rust-lang/rustup"
+
+[[sources]]
+type = "file"
+url = "https://static.rust-lang.org/rustup/archive/${env.version}/rustup-init"
+
+[binaries]
+rustup = "rustup-init"
+
+[actions.setup]
+binary = "rustup"
+args = ["component", "add", "clippy"]
+description = "Install clippy"
+
+[actions.broken]
+binary = "cargo"
+"#;
+        let app = CyreneApp::from_str(config).unwrap();
+
+        let (bin_path, args) = app.action("setup", "1.28.0").unwrap();
+        assert_eq!(bin_path, "rustup-init");
+        assert_eq!(args, vec!["component", "add", "clippy"]);
+        assert!(matches!(
+            app.action("unknown", "1.28.0"),
+            Err(CyreneError::AppActionNotFound(_, _))
+        ));
+        assert!(matches!(
+            app.action("broken", "1.28.0"),
+            Err(CyreneError::ActionBinaryNotFound(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn install_removes_partial_install() {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+        };
+
+        // Serve the first source once, the other sources point to a closed port
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nfirst",
+                )
+                .unwrap();
+            request_line
+        });
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_port = closed.local_addr().unwrap().port();
+        drop(closed);
+
+        let config = format!(
+            r#"
+[settings]
+upgrade_latest = false
+
+[versions]
+type = "github"
+repo = "Damillora/cyrene"
+
+[[sources]]
+type = "file"
+url = "http://127.0.0.1:{port}/first"
+
+[[sources]]
+type = "file"
+url = "http://127.0.0.1:{closed_port}/second"
+
+[[sources]]
+type = "file"
+url = "http://127.0.0.1:{closed_port}/third"
+
+[binaries]
+first = "first"
+"#
+        );
+        let app = CyreneApp::from_str(&config).unwrap();
+        let apps_dir = tempfile::tempdir().unwrap();
+        let installation_dir = apps_dir.path().join("first/1.0.0");
+        fs::create_dir_all(&installation_dir).unwrap();
+
+        assert!(
+            app.install(
+                "1.0.0",
+                &installation_dir,
+                &CyreneConfig::default(),
+                &Cancellation::default()
+            )
+            .await
+            .is_err()
+        );
+        // The first source was downloaded before the install failed
+        assert!(server.join().unwrap().starts_with("GET /first "));
+        assert!(!installation_dir.exists());
+    }
 }
@@ -0,0 +1,69 @@
+use std::{fs::File, io, path::Path};
+
+use sha2::{Digest, Sha256};
+
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Find the checksum of a file in a `sha256sum`-style checksums file
+pub fn find_checksum(checksums: &str, filename: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (checksum, name) = line.split_once(char::is_whitespace)?;
+        // Binary mode entries are prefixed with `*`
+        let name = name.trim().trim_start_matches('*');
+        if name.eq(filename) {
+            Some(checksum.to_lowercase())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cyrene");
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_find_checksum() {
+        let checksums = "\
+aaaa  cyrene-x86_64-unknown-linux-gnu.tar.xz
+BBBB *cyrene-aarch64-apple-darwin.tar.xz
+";
+
+        assert_eq!(
+            find_checksum(checksums, "cyrene-x86_64-unknown-linux-gnu.tar.xz"),
+            Some("aaaa".to_string())
+        );
+        assert_eq!(
+            find_checksum(checksums, "cyrene-aarch64-apple-darwin.tar.xz"),
+            Some("bbbb".to_string())
+        );
+        assert_eq!(find_checksum(checksums, "cyrene.zip"), None);
+    }
+}
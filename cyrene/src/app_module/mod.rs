@@ -1,6 +1,12 @@
+/// Checksum helpers
+pub mod hash;
+/// Platform information
+pub mod platform;
 /// Post-install processor
 pub mod post_install;
 /// Sources processor
 pub mod sources;
+/// Post-install verification
+pub mod verify;
 /// Version processor
 pub mod versions;
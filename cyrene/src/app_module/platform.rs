@@ -0,0 +1,82 @@
+use std::{
+    collections::HashMap,
+    env::consts::{ARCH, OS},
+    sync::LazyLock,
+};
+
+static HOST_OS: LazyLock<String> =
+    LazyLock::new(|| std::env::var("CYRENE_OS").unwrap_or_else(|_| OS.to_string()));
+static HOST_ARCH: LazyLock<String> =
+    LazyLock::new(|| std::env::var("CYRENE_ARCH").unwrap_or_else(|_| ARCH.to_string()));
+
+static TARGET_TRIPLE: LazyLock<String> = LazyLock::new(|| {
+    let os = match os() {
+        "linux" if cfg!(target_env = "musl") => "unknown-linux-musl",
+        "linux" => "unknown-linux-gnu",
+        "macos" => "apple-darwin",
+        "windows" if cfg!(target_env = "gnu") => "pc-windows-gnu",
+        "windows" => "pc-windows-msvc",
+        os => os,
+    };
+    format!("{}-{}", arch(), os)
+});
+
+/// OS of the host, or `CYRENE_OS` to test recipes for another platform
+pub fn os() -> &'static str {
+    &HOST_OS
+}
+
+/// Architecture of the host, or `CYRENE_ARCH` to test recipes for another platform
+pub fn arch() -> &'static str {
+    &HOST_ARCH
+}
+
+/// Rust-style target triple of the host, e.g. `x86_64-unknown-linux-gnu`
+pub fn target_triple() -> &'static str {
+    &TARGET_TRIPLE
+}
+
+/// Go-style OS name of the host, e.g. `darwin` instead of `macos`
+pub fn go_os() -> &'static str {
+    match os() {
+        "macos" => "darwin",
+        os => os,
+    }
+}
+
+/// Go-style architecture name of the host, e.g. `amd64` instead of `x86_64`
+pub fn go_arch() -> &'static str {
+    match arch() {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        arch => arch,
+    }
+}
+
+/// Values available to templates in app recipes
+pub fn template_values(version: &str) -> HashMap<&str, &str> {
+    let mut values = HashMap::new();
+    values.insert("version", version);
+    values.insert("triple", target_triple());
+    values.insert("os", os());
+    values.insert("arch", arch());
+    values.insert("go_os", go_os());
+    values.insert("go_arch", go_arch());
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_triple() {
+        let triple = target_triple();
+        assert!(triple.starts_with(arch()));
+        if os() == "linux" {
+            assert!(triple.contains("-unknown-linux-"));
+        }
+    }
+}
@@ -1,8 +1,12 @@
-use std::{collections::HashMap, fs::File, os::unix::fs::PermissionsExt, path::Path};
+use std::{fs::File, os::unix::fs::PermissionsExt, path::Path};
 
 use text_template::Template;
 
-use crate::{app::AppPostInstallCommands, errors::CyreneError};
+use crate::{
+    app::AppPostInstallCommands,
+    app_module::{hash, platform, sources},
+    errors::CyreneError,
+};
 
 fn set_exec(path: &str, dest: &Path) -> Result<(), CyreneError> {
     let mut target_file = dest.to_path_buf();
@@ -16,19 +20,75 @@ fn set_exec(path: &str, dest: &Path) -> Result<(), CyreneError> {
     Ok(())
 }
 
+async fn verify_sha256(
+    path: &str,
+    checksums_url: &str,
+    dest: &Path,
+    client: &reqwest::Client,
+) -> Result<(), CyreneError> {
+    let mut target_file = dest.to_path_buf();
+    target_file.push(path);
+    let filename = target_file
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let checksums = client
+        .get(checksums_url)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|e| CyreneError::ChecksumFetch(checksums_url.to_string(), e))?
+        .text()
+        .await
+        .map_err(|e| CyreneError::ChecksumFetch(checksums_url.to_string(), e))?;
+    let expected = hash::find_checksum(&checksums, &filename).ok_or(
+        CyreneError::ChecksumNotFound(filename.clone(), checksums_url.to_string()),
+    )?;
+    let actual = hash::sha256_file(&target_file)
+        .map_err(|e| CyreneError::ChecksumRead(target_file.clone(), e))?;
+    if !expected.eq(&actual) {
+        return Err(CyreneError::ChecksumMismatch(filename, expected, actual));
+    }
+
+    Ok(())
+}
+
 pub async fn process_post_install(
     command: &AppPostInstallCommands,
     version: &str,
     dest: &Path,
+    client: &reqwest::Client,
 ) -> Result<(), CyreneError> {
-    let mut values = HashMap::new();
-    values.insert("version", version);
+    let values = platform::template_values(version);
     match command {
         AppPostInstallCommands::SetExec { path } => {
             let path_tmpl = Template::from(path.as_str());
             let path = path_tmpl.fill_in(&values).to_string();
             set_exec(&path, dest)?
         }
+        AppPostInstallCommands::VerifySha256 {
+            path,
+            checksums_url,
+        } => {
+            let path_tmpl = Template::from(path.as_str());
+            let path = path_tmpl.fill_in(&values).to_string();
+            let url_tmpl = Template::from(checksums_url.as_str());
+            let checksums_url = url_tmpl.fill_in(&values).to_string();
+            verify_sha256(&path, &checksums_url, dest, client).await?
+        }
+        AppPostInstallCommands::VerifyGpg {
+            path,
+            signature_url,
+            public_key,
+        } => {
+            let path_tmpl = Template::from(path.as_str());
+            let mut target_file = dest.to_path_buf();
+            target_file.push(path_tmpl.fill_in(&values).to_string());
+            let url_tmpl = Template::from(signature_url.as_str());
+            let signature_url = url_tmpl.fill_in(&values).to_string();
+            sources::verify_gpg(&target_file, &signature_url, public_key, client).await?
+        }
     };
 
     Ok(())
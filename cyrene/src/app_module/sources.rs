@@ -1,20 +1,71 @@
 use std::{
-    collections::HashMap,
     io::{self},
-    path::Path,
+    path::{Component, Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
 };
 
-use async_compression::futures::{bufread::GzipDecoder, bufread::XzDecoder};
+use async_compression::futures::{
+    bufread::BzDecoder, bufread::GzipDecoder, bufread::XzDecoder, bufread::ZstdDecoder,
+};
 use async_tar::Archive;
-use futures::TryStreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
+use futures::{AsyncBufRead, AsyncBufReadExt, AsyncRead, TryStreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use log::debug;
+use pgp::{Deserializable, SignedPublicKey, StandaloneSignature};
 use tempfile::tempfile;
 use text_template::Template;
+use tokio::sync::RwLock;
 use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
 use zip::ZipArchive;
 
-use crate::{app::AppSources, errors::CyreneError};
+use crate::{
+    app::AppSources,
+    app_module::{hash, platform},
+    errors::CyreneError,
+    output::{self, CyreneEvent},
+};
+
+/// Progress of downloading all sources of an install
+pub struct SourceProgress {
+    multi: MultiProgress,
+    current: usize,
+    total: usize,
+}
+
+impl SourceProgress {
+    pub fn new(total: usize) -> Self {
+        let multi = MultiProgress::new();
+        if output::is_quiet() {
+            multi.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        Self {
+            multi,
+            current: 0,
+            total,
+        }
+    }
+
+    /// Move on to the next source
+    pub fn advance(&mut self) {
+        self.current += 1;
+    }
+
+    fn add(&self, filename: &str, len: Option<u64>) -> ProgressBar {
+        let message = if self.total > 1 {
+            format!("[{}/{}] {}", self.current, self.total, filename)
+        } else {
+            filename.to_string()
+        };
+        match len {
+            Some(len) => self.multi.add(new_progress_bar(&message, len)),
+            None => self.multi.add(new_spinner(&message)),
+        }
+    }
+}
 
 fn new_progress_bar(filename: &str, len: u64) -> ProgressBar {
     ProgressBar::new(len)
@@ -27,135 +78,572 @@ fn new_progress_bar(filename: &str, len: u64) -> ProgressBar {
         .with_message(filename.to_string())
 }
 
-async fn from_tar_xz(url: &str, dest: &Path) -> Result<(), CyreneError> {
-    let target_filename = url
-        .trim_end_matches('/')
-        .split('/')
-        .next_back()
-        .unwrap()
-        .to_string();
-    let client = reqwest::Client::new();
-    let res = client
-        .get(url)
-        .send()
-        .await
-        .map_err(|e| CyreneError::Download(url.to_string(), e))?;
-    let len = res.content_length().unwrap();
+/// Progress of a download whose length is unknown
+fn new_spinner(filename: &str) -> ProgressBar {
+    let pb = ProgressBar::new_spinner()
+        .with_style(
+            ProgressStyle::with_template(
+                "{msg:.white.bold} {spinner:.219} [{bytes:>10.white}] {binary_bytes_per_sec:.219.bold}",
+            )
+            .unwrap(),
+        )
+        .with_message(filename.to_string());
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
 
-    let reader = res
-        .bytes_stream()
-        .map_err(io::Error::other)
-        .into_async_read()
-        .compat();
-    let pb = new_progress_bar(&target_filename, len);
-    let reader = pb.wrap_async_read(reader);
+/// Archive formats recognized by their magic bytes
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Bzip2,
+    Gzip,
+    Xz,
+    Zip,
+    Zstd,
+}
+
+pub fn detect_archive(bytes: &[u8]) -> Option<ArchiveKind> {
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        Some(ArchiveKind::Gzip)
+    } else if bytes.starts_with(&[0xfd, 0x37, 0x7a, 0x58, 0x5a]) {
+        Some(ArchiveKind::Xz)
+    } else if bytes.starts_with(&[0x50, 0x4b]) {
+        Some(ArchiveKind::Zip)
+    } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some(ArchiveKind::Zstd)
+    } else if bytes.starts_with(b"BZh") {
+        Some(ArchiveKind::Bzip2)
+    } else {
+        None
+    }
+}
+
+/// Formats a source can be unpacked from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    TarGz,
+    TarXz,
+    TarZst,
+    TarBz2,
+    Zip,
+    Raw,
+}
+
+impl ArchiveFormat {
+    /// Guesses the format from a file name, treating anything unrecognized as a raw file
+    pub fn from_filename(filename: &str) -> Self {
+        if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
+            ArchiveFormat::TarGz
+        } else if filename.ends_with(".tar.xz") || filename.ends_with(".txz") {
+            ArchiveFormat::TarXz
+        } else if filename.ends_with(".tar.zst") || filename.ends_with(".tzst") {
+            ArchiveFormat::TarZst
+        } else if filename.ends_with(".tar.bz2") || filename.ends_with(".tbz2") {
+            ArchiveFormat::TarBz2
+        } else if filename.ends_with(".zip") {
+            ArchiveFormat::Zip
+        } else {
+            ArchiveFormat::Raw
+        }
+    }
+}
+
+/// Drops the first `strip_components` components of an archive entry path.
+///
+/// Returns `None` for entries that are stripped away entirely or would escape the destination.
+fn strip_path(path: &Path, strip_components: usize) -> Option<PathBuf> {
+    let path: PathBuf = path
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .skip(strip_components)
+        .collect();
+    if path.as_os_str().is_empty()
+        || path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Stops blocking extraction of an abandoned install, e.g. after a timeout
+#[derive(Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+    // Held for reading by each running extraction
+    running: Arc<RwLock<()>>,
+}
 
-    let tar_xz = XzDecoder::new(reader.compat());
-    let tar = Archive::new(tar_xz);
-    tar.unpack(dest).await.unwrap();
+impl Cancellation {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Cancel running extractions and wait until they have stopped
+    pub async fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        let _stopped = self.running.write().await;
+    }
+}
+
+/// Which entries of an archive are unpacked, and where
+#[derive(Default)]
+pub struct ExtractOptions<'a> {
+    /// Leading path components dropped from entries
+    pub strip_components: usize,
+    /// Entry paths or path prefixes to unpack, everything is unpacked when empty
+    pub members: &'a [String],
+}
+
+impl ExtractOptions<'_> {
+    /// Whether every entry is unpacked at its own path
+    fn unpacks_all(&self) -> bool {
+        self.strip_components == 0 && self.members.is_empty()
+    }
+
+    /// Path to unpack an entry to, marking the members it matches as found.
+    ///
+    /// Returns `None` for entries that are skipped.
+    fn entry_path(&self, path: &Path, found: &mut [bool]) -> Option<PathBuf> {
+        let path = strip_path(path, 0)?;
+        if !self.members.is_empty() {
+            let mut matched = false;
+            for (member, found) in self.members.iter().zip(found.iter_mut()) {
+                if path.starts_with(member.trim_start_matches("./")) {
+                    *found = true;
+                    matched = true;
+                }
+            }
+            if !matched {
+                return None;
+            }
+        }
+
+        strip_path(&path, self.strip_components)
+    }
+
+    /// Fails on the first member that matched no entry
+    fn check_found(&self, found: &[bool], source: &str) -> Result<(), CyreneError> {
+        match self.members.iter().zip(found).find(|(_, found)| !**found) {
+            Some((member, _)) => Err(CyreneError::ArchiveMemberNotFound(
+                member.clone(),
+                source.to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Unpacks the entries of a tarball selected by `options`
+async fn unpack_entries<R: AsyncRead + Unpin>(
+    archive: Archive<R>,
+    dest: &Path,
+    options: &ExtractOptions<'_>,
+    found: &mut [bool],
+) -> io::Result<()> {
+    if options.unpacks_all() {
+        return archive.unpack(dest).await;
+    }
+
+    let mut entries = archive.entries()?;
+    while let Some(mut entry) = entries.try_next().await? {
+        // async-tar paths are async-std paths
+        let entry_path = entry.path()?;
+        let Some(path) = options.entry_path(AsRef::<Path>::as_ref(&*entry_path), found) else {
+            continue;
+        };
+        let target = dest.join(path);
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        entry.unpack(&target).await?;
+    }
 
     Ok(())
 }
 
-async fn from_tar_gz(url: &str, dest: &Path) -> Result<(), CyreneError> {
-    let target_filename = url
-        .trim_end_matches('/')
+/// Unpacks a compressed tarball, picking the decoder from its magic bytes rather than its name.
+async fn unpack_tar<R: AsyncBufRead + Unpin>(
+    mut reader: R,
+    source: &str,
+    dest: &Path,
+    options: &ExtractOptions<'_>,
+) -> Result<(), CyreneError> {
+    let kind = detect_archive(
+        reader
+            .fill_buf()
+            .await
+            .map_err(|e| CyreneError::DownloadWrite(source.to_string(), e))?,
+    );
+    debug!("Detected {:?} in {}", kind, source);
+    let mut found = vec![false; options.members.len()];
+    let unpacked = match kind {
+        Some(ArchiveKind::Gzip) => {
+            let archive = Archive::new(GzipDecoder::new(reader));
+            unpack_entries(archive, dest, options, &mut found).await
+        }
+        Some(ArchiveKind::Xz) => {
+            let archive = Archive::new(XzDecoder::new(reader));
+            unpack_entries(archive, dest, options, &mut found).await
+        }
+        Some(ArchiveKind::Zstd) => {
+            // Parallel compressors may split the tarball into several frames
+            let mut decoder = ZstdDecoder::new(reader);
+            decoder.multiple_members(true);
+            let archive = Archive::new(decoder);
+            unpack_entries(archive, dest, options, &mut found).await
+        }
+        Some(ArchiveKind::Bzip2) => {
+            let archive = Archive::new(BzDecoder::new(reader));
+            unpack_entries(archive, dest, options, &mut found).await
+        }
+        Some(ArchiveKind::Zip) => return Err(CyreneError::ArchiveIsZip(source.to_string())),
+        None => return Err(CyreneError::UnknownArchiveFormat(source.to_string())),
+    };
+    unpacked.map_err(|e| CyreneError::DownloadWrite(source.to_string(), e))?;
+
+    options.check_found(&found, source)
+}
+
+/// Extracts the entries of a ZIP archive selected by `options`.
+///
+/// Entries are streamed one by one from the archive file, stopping before the next entry once
+/// `cancel` is cancelled.
+fn extract_zip(
+    file: std::fs::File,
+    dest: &Path,
+    options: &ExtractOptions<'_>,
+    source: &str,
+    cancel: &Cancellation,
+) -> Result<(), CyreneError> {
+    let write_error = |e: io::Error| CyreneError::DownloadWrite(source.to_string(), e);
+    let mut zip_file = ZipArchive::new(file).map_err(|e| write_error(io::Error::other(e)))?;
+
+    let mut found = vec![false; options.members.len()];
+    for i in 0..zip_file.len() {
+        if cancel.is_cancelled() {
+            return Err(write_error(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "extraction cancelled",
+            )));
+        }
+        let mut entry = zip_file
+            .by_index(i)
+            .map_err(|e| write_error(io::Error::other(e)))?;
+        let Some(path) = entry
+            .enclosed_name()
+            .and_then(|path| options.entry_path(&path, &mut found))
+        else {
+            continue;
+        };
+        let target = dest.join(path);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&target).map_err(write_error)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(write_error)?;
+        }
+        #[cfg(unix)]
+        if entry.is_symlink() {
+            let mut link_target = String::new();
+            io::Read::read_to_string(&mut entry, &mut link_target).map_err(write_error)?;
+            std::os::unix::fs::symlink(link_target, &target).map_err(write_error)?;
+            continue;
+        }
+        let mut file = std::fs::File::create(&target).map_err(write_error)?;
+        io::copy(&mut entry, &mut file).map_err(write_error)?;
+
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&target, std::fs::Permissions::from_mode(mode))
+                .map_err(write_error)?;
+        }
+    }
+
+    options.check_found(&found, source)
+}
+
+/// Unpacks `reader` into `dest` according to `format`.
+///
+/// Archive entries are selected and renamed by `options`, while raw files are written to `dest`
+/// as `filename`. Tarballs are decoded according to their magic bytes, so the compression named
+/// by `format` is only a hint.
+pub async fn extract<R: tokio::io::AsyncBufRead + Unpin>(
+    format: ArchiveFormat,
+    mut reader: R,
+    filename: &str,
+    dest: &Path,
+    options: &ExtractOptions<'_>,
+    source: &str,
+    cancel: &Cancellation,
+) -> Result<(), CyreneError> {
+    let write_error = |e| CyreneError::DownloadWrite(source.to_string(), e);
+    match format {
+        ArchiveFormat::TarGz
+        | ArchiveFormat::TarXz
+        | ArchiveFormat::TarZst
+        | ArchiveFormat::TarBz2 => unpack_tar(reader.compat(), source, dest, options).await,
+        ArchiveFormat::Zip => {
+            // ZIP archives need random access to their central directory, so they are spooled to
+            // a temporary file on disk before being extracted
+            let mut file = tokio::fs::File::from_std(tempfile().map_err(write_error)?);
+            tokio::io::copy(&mut reader, &mut file)
+                .await
+                .map_err(write_error)?;
+            let file = file.into_std().await;
+            let (dest, source) = (dest.to_path_buf(), source.to_string());
+            let (strip_components, members) = (options.strip_components, options.members.to_vec());
+            let running = cancel.running.clone().read_owned().await;
+            let cancel = cancel.clone();
+            // Extraction blocks, so it runs on its own thread to let install timeouts fire. The
+            // thread keeps running when the install is dropped, until `cancel` stops it
+            tokio::task::spawn_blocking(move || {
+                let _running = running;
+                let options = ExtractOptions {
+                    strip_components,
+                    members: &members,
+                };
+                extract_zip(file, &dest, &options, &source, &cancel)
+            })
+            .await
+            .map_err(|e| write_error(io::Error::other(e)))?
+        }
+        ArchiveFormat::Raw => {
+            let target_file = dest.join(filename);
+            let mut file = tokio::fs::File::create(&target_file).await.map_err(|e| {
+                CyreneError::DownloadWrite(target_file.to_string_lossy().to_string(), e)
+            })?;
+            tokio::io::copy(&mut reader, &mut file)
+                .await
+                .map_err(write_error)?;
+            Ok(())
+        }
+    }
+}
+
+/// File name of a download, taken from the last segment of `url`
+fn url_filename(url: &str) -> String {
+    url.trim_end_matches('/')
         .split('/')
         .next_back()
         .unwrap()
-        .to_string();
-    let client = reqwest::Client::new();
+        .to_string()
+}
+
+/// Starts downloading `url`, returning its file name and a reader that reports progress
+async fn download(
+    url: &str,
+    progress: &SourceProgress,
+    client: &reqwest::Client,
+) -> Result<(String, impl tokio::io::AsyncBufRead + Unpin), CyreneError> {
+    let target_filename = url_filename(url);
     let res = client
         .get(url)
         .send()
         .await
         .map_err(|e| CyreneError::Download(url.to_string(), e))?;
-    let len = res.content_length().unwrap();
+    let len = res.content_length();
+    output::emit(CyreneEvent::DownloadStarted { url, len });
 
     let reader = res
         .bytes_stream()
         .map_err(io::Error::other)
         .into_async_read()
         .compat();
-    debug!("len: {}", len);
-    let pb = new_progress_bar(&target_filename, len);
-    let reader = pb.wrap_async_read(reader);
+    let pb = progress.add(&target_filename, len);
+
+    Ok((target_filename, pb.wrap_async_read(reader)))
+}
 
-    let tar_gz = GzipDecoder::new(reader.compat());
-    let tar = Archive::new(tar_gz);
-    tar.unpack(dest).await.unwrap();
+/// Downloads `url` and unpacks it into `dest`.
+///
+/// The download is streamed straight into the decoder or destination file, except for ZIP
+/// archives which are spooled to disk first.
+async fn from_url(
+    url: &str,
+    format: ArchiveFormat,
+    dest: &Path,
+    options: &ExtractOptions<'_>,
+    progress: &SourceProgress,
+    client: &reqwest::Client,
+    cancel: &Cancellation,
+) -> Result<(), CyreneError> {
+    let (target_filename, reader) = download(url, progress, client).await?;
+    extract(format, reader, &target_filename, dest, options, url, cancel).await?;
+    output::emit(CyreneEvent::DownloadFinished { url });
 
     Ok(())
 }
 
-async fn from_zip(url: &str, dest: &Path) -> Result<(), CyreneError> {
-    let target_filename = url
-        .trim_end_matches('/')
-        .split('/')
-        .next_back()
-        .unwrap()
-        .to_string();
-    let client = reqwest::Client::new();
-    let res = client
-        .get(url)
-        .send()
+/// Decompresses a single file into `target_file` and marks it executable
+async fn decompress_exec<R: AsyncBufRead + Unpin>(
+    reader: R,
+    kind: ArchiveKind,
+    target_file: &Path,
+    source: &str,
+) -> Result<(), CyreneError> {
+    let write_error = |e| CyreneError::DownloadWrite(target_file.to_string_lossy().to_string(), e);
+    let mut file = tokio::fs::File::create(target_file)
         .await
-        .map_err(|e| CyreneError::Download(url.to_string(), e))?;
-    let len = res.content_length().unwrap();
+        .map_err(write_error)?;
+    match kind {
+        ArchiveKind::Gzip => {
+            tokio::io::copy(&mut GzipDecoder::new(reader).compat(), &mut file)
+                .await
+                .map_err(write_error)?;
+        }
+        ArchiveKind::Xz => {
+            tokio::io::copy(&mut XzDecoder::new(reader).compat(), &mut file)
+                .await
+                .map_err(write_error)?;
+        }
+        _ => return Err(CyreneError::UnknownArchiveFormat(source.to_string())),
+    }
 
-    let reader = res
-        .bytes_stream()
-        .map_err(io::Error::other)
-        .into_async_read()
-        .compat();
-    let pb = new_progress_bar(&target_filename, len);
-    let mut reader = pb.wrap_async_read(reader);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(target_file, std::fs::Permissions::from_mode(0o755))
+            .await
+            .map_err(write_error)?;
+    }
 
-    let mut file = tokio::fs::File::from_std(tempfile().unwrap());
+    Ok(())
+}
 
-    tokio::io::copy(&mut reader, &mut file)
-        .await
-        .map_err(|e| CyreneError::DownloadWrite(url.to_string(), e))?;
+/// Downloads and decompresses a single compressed executable.
+///
+/// The download is streamed through the decoder into the destination file.
+async fn from_compressed_exec(
+    url: &str,
+    path: Option<&str>,
+    kind: ArchiveKind,
+    dest: &Path,
+    progress: &SourceProgress,
+    client: &reqwest::Client,
+) -> Result<(), CyreneError> {
+    let (target_filename, reader) = download(url, progress, client).await?;
 
-    let mut zip_file = ZipArchive::new(file.into_std().await).unwrap();
-    zip_file.extract(dest).unwrap();
+    let mut target_file = dest.to_path_buf();
+    match path {
+        Some(path) => target_file.push(path),
+        None => target_file.push(
+            target_filename
+                .trim_end_matches(".gz")
+                .trim_end_matches(".xz"),
+        ),
+    }
+    decompress_exec(reader.compat(), kind, &target_file, url).await?;
+    output::emit(CyreneEvent::DownloadFinished { url });
 
     Ok(())
 }
 
-async fn from_file(url: &str, dest: &Path) -> Result<(), CyreneError> {
-    let target_filename = url
-        .trim_end_matches('/')
-        .split('/')
-        .next_back()
-        .unwrap()
-        .to_string();
-    let client = reqwest::Client::new();
-    let res = client
-        .get(url)
-        .send()
+/// Installs from a file on disk, detecting archives from the file name.
+///
+/// Archives are read and unpacked the same way as their downloaded counterparts.
+pub async fn from_local(
+    path: &Path,
+    dest: &Path,
+    cancel: &Cancellation,
+) -> Result<(), CyreneError> {
+    let filename = path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let file = tokio::fs::File::open(path)
         .await
-        .map_err(|e| CyreneError::Download(url.to_string(), e))?;
-    let len = res.content_length().unwrap();
+        .map_err(|e| CyreneError::LocalSourceRead(path.to_path_buf(), e))?;
 
-    let reader = res
-        .bytes_stream()
-        .map_err(io::Error::other)
-        .into_async_read()
-        .compat();
-    let pb = new_progress_bar(&target_filename, len);
-    let mut reader = pb.wrap_async_read(reader);
+    extract(
+        ArchiveFormat::from_filename(&filename),
+        tokio::io::BufReader::new(file),
+        &filename,
+        dest,
+        &ExtractOptions::default(),
+        &path.to_string_lossy(),
+        cancel,
+    )
+    .await
+}
 
-    let mut target_file = dest.to_path_buf();
-    target_file.push(&target_filename);
+/// Checks a detached signature against an ASCII-armored public key or any of its subkeys
+fn verify_signature(data: &[u8], signature: &[u8], public_key: &str) -> Result<(), String> {
+    let (public_key, _) = SignedPublicKey::from_string(public_key).map_err(|e| e.to_string())?;
+    let signature = if signature.starts_with(b"-----BEGIN") {
+        let signature = String::from_utf8_lossy(signature);
+        StandaloneSignature::from_string(&signature)
+            .map_err(|e| e.to_string())?
+            .0
+    } else {
+        StandaloneSignature::from_bytes(signature).map_err(|e| e.to_string())?
+    };
+
+    if signature.verify(&public_key, data).is_ok()
+        || public_key
+            .public_subkeys
+            .iter()
+            .any(|subkey| signature.verify(subkey, data).is_ok())
+    {
+        Ok(())
+    } else {
+        Err("signature does not match".to_string())
+    }
+}
 
-    let mut file = tokio::fs::File::create(&target_file)
+/// Downloads a detached signature and verifies `file` against it.
+///
+/// The signature may be binary or ASCII-armored.
+pub async fn verify_gpg(
+    file: &Path,
+    sig_url: &str,
+    public_key: &str,
+    client: &reqwest::Client,
+) -> Result<(), CyreneError> {
+    let signature = client
+        .get(sig_url)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|e| CyreneError::SignatureFetch(sig_url.to_string(), e))?
+        .bytes()
         .await
-        .map_err(|e| CyreneError::DownloadWrite(target_file.to_string_lossy().to_string(), e))?;
+        .map_err(|e| CyreneError::SignatureFetch(sig_url.to_string(), e))?;
 
-    tokio::io::copy(&mut reader, &mut file)
+    verify_file_signature(file, &signature, public_key).await
+}
+
+/// Verifies `file` against a detached signature already downloaded
+async fn verify_file_signature(
+    file: &Path,
+    signature: &[u8],
+    public_key: &str,
+) -> Result<(), CyreneError> {
+    let data = tokio::fs::read(file)
         .await
-        .map_err(|e| CyreneError::DownloadWrite(url.to_string(), e))?;
+        .map_err(|e| CyreneError::SignatureRead(file.to_path_buf(), e))?;
+
+    verify_signature(&data, signature, public_key)
+        .map_err(|e| CyreneError::SignatureVerify(file.to_path_buf(), e))
+}
+
+/// Check a downloaded file against its expected SHA-256 digest, removing it on mismatch
+fn verify_download(path: &Path, expected: &str) -> Result<(), CyreneError> {
+    let actual =
+        hash::sha256_file(path).map_err(|e| CyreneError::ChecksumRead(path.to_path_buf(), e))?;
+    let expected = expected.to_lowercase();
+    if !expected.eq(&actual) {
+        let _ = std::fs::remove_file(path);
+        let filename = path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        return Err(CyreneError::ChecksumMismatch(filename, expected, actual));
+    }
 
     Ok(())
 }
@@ -164,29 +652,550 @@ pub async fn process_source(
     source: &AppSources,
     version: &str,
     dest: &Path,
+    progress: &SourceProgress,
+    client: &reqwest::Client,
+    cancel: &Cancellation,
 ) -> Result<(), CyreneError> {
-    let mut values = HashMap::new();
-    values.insert("version", version);
+    let values = platform::template_values(version);
     match source {
-        AppSources::TarXz { url } => {
-            let tmpl = Template::from(url.as_str());
-            let url = tmpl.fill_in(&values);
-            from_tar_xz(&url.to_string(), dest).await
+        AppSources::TarXz {
+            url,
+            strip_components,
+            members,
+        }
+        | AppSources::TarGz {
+            url,
+            strip_components,
+            members,
+        }
+        | AppSources::TarZst {
+            url,
+            strip_components,
+            members,
+        }
+        | AppSources::TarBz2 {
+            url,
+            strip_components,
+            members,
         }
-        AppSources::TarGz { url } => {
+        | AppSources::Zip {
+            url,
+            strip_components,
+            members,
+        } => {
+            let format = match source {
+                AppSources::TarXz { .. } => ArchiveFormat::TarXz,
+                AppSources::TarGz { .. } => ArchiveFormat::TarGz,
+                AppSources::TarZst { .. } => ArchiveFormat::TarZst,
+                AppSources::TarBz2 { .. } => ArchiveFormat::TarBz2,
+                _ => ArchiveFormat::Zip,
+            };
             let tmpl = Template::from(url.as_str());
             let url = tmpl.fill_in(&values);
-            from_tar_gz(&url.to_string(), dest).await
+            let options = ExtractOptions {
+                strip_components: *strip_components,
+                members,
+            };
+            from_url(
+                &url.to_string(),
+                format,
+                dest,
+                &options,
+                progress,
+                client,
+                cancel,
+            )
+            .await
         }
-        AppSources::Zip { url } => {
+        AppSources::File { url, sha256 } => {
             let tmpl = Template::from(url.as_str());
-            let url = tmpl.fill_in(&values);
-            from_zip(&url.to_string(), dest).await
+            let url = tmpl.fill_in(&values).to_string();
+            from_url(
+                &url,
+                ArchiveFormat::Raw,
+                dest,
+                &ExtractOptions::default(),
+                progress,
+                client,
+                cancel,
+            )
+            .await?;
+            if let Some(sha256) = sha256 {
+                verify_download(&dest.join(url_filename(&url)), sha256)?;
+            }
+
+            Ok(())
         }
-        AppSources::File { url } => {
+        AppSources::GzExec { url, path } | AppSources::XzExec { url, path } => {
+            let kind = match source {
+                AppSources::GzExec { .. } => ArchiveKind::Gzip,
+                _ => ArchiveKind::Xz,
+            };
             let tmpl = Template::from(url.as_str());
             let url = tmpl.fill_in(&values);
-            from_file(&url.to_string(), dest).await
+            let path = path
+                .as_ref()
+                .map(|f| Template::from(f.as_str()).fill_in(&values).to_string());
+            from_compressed_exec(
+                &url.to_string(),
+                path.as_deref(),
+                kind,
+                dest,
+                progress,
+                client,
+            )
+            .await
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        io::Write,
+    };
+
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+
+    #[test]
+    fn test_detect_archive() {
+        assert_eq!(
+            detect_archive(&[0x1f, 0x8b, 0x08, 0x00]),
+            Some(ArchiveKind::Gzip)
+        );
+        assert_eq!(
+            detect_archive(&[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]),
+            Some(ArchiveKind::Xz)
+        );
+        assert_eq!(
+            detect_archive(&[0x50, 0x4b, 0x03, 0x04]),
+            Some(ArchiveKind::Zip)
+        );
+        assert_eq!(
+            detect_archive(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Some(ArchiveKind::Zstd)
+        );
+        assert_eq!(detect_archive(b"BZh91AY&SY"), Some(ArchiveKind::Bzip2));
+        assert_eq!(detect_archive(b"<!DOCTYPE html>"), None);
+        assert_eq!(detect_archive(&[]), None);
+    }
+
+    /// Tracks the bytes allocated by each thread, so tests can bound the memory they use
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+        static PEAK_ALLOCATED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc(layout) };
+            if !ptr.is_null() {
+                let _ = ALLOCATED.try_with(|allocated| {
+                    allocated.set(allocated.get() + layout.size());
+                    let _ =
+                        PEAK_ALLOCATED.try_with(|peak| peak.set(peak.get().max(allocated.get())));
+                });
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) };
+            let _ = ALLOCATED
+                .try_with(|allocated| allocated.set(allocated.get().saturating_sub(layout.size())));
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn test_extract_zip_large_entry_streamed() {
+        let chunk = vec![0u8; 1024 * 1024];
+        let chunks = 256;
+
+        let mut zip = ZipWriter::new(tempfile().unwrap());
+        zip.start_file(
+            "large.bin",
+            SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Stored)
+                .large_file(true),
+        )
+        .unwrap();
+        for _ in 0..chunks {
+            zip.write_all(&chunk).unwrap();
         }
+        let file = zip.finish().unwrap();
+
+        // Extraction runs on this thread, so its peak allocation is what extraction allocated
+        let dest = tempfile::tempdir().unwrap();
+        let before = ALLOCATED.with(Cell::get);
+        PEAK_ALLOCATED.with(|peak| peak.set(before));
+        extract_zip(
+            file,
+            dest.path(),
+            &ExtractOptions::default(),
+            "large.zip",
+            &Cancellation::default(),
+        )
+        .unwrap();
+        let peak = PEAK_ALLOCATED.with(Cell::get) - before;
+        assert!(peak < 4 * 1024 * 1024, "extraction allocated {peak} bytes");
+
+        let extracted = std::fs::metadata(dest.path().join("large.bin")).unwrap();
+        assert_eq!(extracted.len(), (chunk.len() * chunks) as u64);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_zip_extraction() {
+        let mut zip = ZipWriter::new(tempfile().unwrap());
+        zip.start_file("bin/cyrene", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"cyrene").unwrap();
+        let file = zip.finish().unwrap();
+
+        // Cancelling waits for running extractions to finish
+        let cancel = Cancellation::default();
+        let running = cancel.running.clone().read_owned().await;
+        let cancelled = tokio::spawn({
+            let cancel = cancel.clone();
+            async move { cancel.cancel().await }
+        });
+        while !cancel.is_cancelled() {
+            tokio::task::yield_now().await;
+        }
+        assert!(!cancelled.is_finished());
+        drop(running);
+        cancelled.await.unwrap();
+
+        let dest = tempfile::tempdir().unwrap();
+        let result = extract_zip(
+            file,
+            dest.path(),
+            &ExtractOptions::default(),
+            "cyrene.zip",
+            &cancel,
+        );
+        assert!(matches!(
+            result,
+            Err(CyreneError::DownloadWrite(_, e)) if e.kind() == io::ErrorKind::Interrupted
+        ));
+        assert!(!dest.path().join("bin/cyrene").exists());
+    }
+
+    #[test]
+    fn test_archive_format_from_filename() {
+        assert_eq!(
+            ArchiveFormat::from_filename("cyrene.tar.gz"),
+            ArchiveFormat::TarGz
+        );
+        assert_eq!(
+            ArchiveFormat::from_filename("cyrene.tgz"),
+            ArchiveFormat::TarGz
+        );
+        assert_eq!(
+            ArchiveFormat::from_filename("cyrene.tar.bz2"),
+            ArchiveFormat::TarBz2
+        );
+        assert_eq!(
+            ArchiveFormat::from_filename("cyrene.zip"),
+            ArchiveFormat::Zip
+        );
+        assert_eq!(ArchiveFormat::from_filename("cyrene"), ArchiveFormat::Raw);
+    }
+
+    #[test]
+    fn test_strip_path() {
+        assert_eq!(
+            strip_path(Path::new("cyrene-1.0.0/bin/cyrene"), 1),
+            Some(PathBuf::from("bin/cyrene"))
+        );
+        assert_eq!(
+            strip_path(Path::new("./cyrene-1.0.0/bin/cyrene"), 1),
+            Some(PathBuf::from("bin/cyrene"))
+        );
+        assert_eq!(strip_path(Path::new("cyrene-1.0.0/"), 1), None);
+        assert_eq!(strip_path(Path::new("cyrene-1.0.0/../../etc"), 1), None);
+    }
+
+    fn tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *contents).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    async fn gzipped_tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+        use async_compression::futures::bufread::GzipEncoder;
+        use futures::AsyncReadExt;
+
+        let tarball = tarball(files);
+        let mut compressed = Vec::new();
+        GzipEncoder::new(&tarball[..])
+            .read_to_end(&mut compressed)
+            .await
+            .unwrap();
+
+        compressed
+    }
+
+    #[tokio::test]
+    async fn test_extract_tar_strip_components() {
+        let binary = b"#!/bin/sh\necho cyrene\n";
+        let compressed = gzipped_tarball(&[("cyrene-1.0.0/bin/cyrene", &binary[..])]).await;
+
+        let dest = tempfile::tempdir().unwrap();
+        let options = ExtractOptions {
+            strip_components: 1,
+            ..Default::default()
+        };
+        extract(
+            ArchiveFormat::TarGz,
+            &compressed[..],
+            "cyrene-1.0.0.tar.gz",
+            dest.path(),
+            &options,
+            "cyrene-1.0.0.tar.gz",
+            &Cancellation::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read(dest.path().join("bin/cyrene")).unwrap(),
+            binary
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_multi_frame_zstd() {
+        use async_compression::futures::bufread::ZstdEncoder;
+        use futures::AsyncReadExt;
+
+        let binary = b"#!/bin/sh\necho cyrene\n";
+        let tarball = tarball(&[
+            ("README.md", &b"# Cyrene\n"[..]),
+            ("bin/cyrene", &binary[..]),
+        ]);
+        let mut compressed = Vec::new();
+        for frame in tarball.chunks(tarball.len() / 2) {
+            ZstdEncoder::new(frame)
+                .read_to_end(&mut compressed)
+                .await
+                .unwrap();
+        }
+
+        let dest = tempfile::tempdir().unwrap();
+        extract(
+            ArchiveFormat::TarZst,
+            &compressed[..],
+            "cyrene-1.0.0.tar.zst",
+            dest.path(),
+            &ExtractOptions::default(),
+            "cyrene-1.0.0.tar.zst",
+            &Cancellation::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read(dest.path().join("bin/cyrene")).unwrap(),
+            binary
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_tar_members() {
+        let binary = b"#!/bin/sh\necho cyrene\n";
+        let compressed = gzipped_tarball(&[
+            ("cyrene-1.0.0/README.md", &b"# Cyrene\n"[..]),
+            ("cyrene-1.0.0/bin/cyrene", &binary[..]),
+        ])
+        .await;
+
+        let dest = tempfile::tempdir().unwrap();
+        let members = vec!["cyrene-1.0.0/bin".to_string()];
+        let options = ExtractOptions {
+            strip_components: 1,
+            members: &members,
+        };
+        extract(
+            ArchiveFormat::TarGz,
+            &compressed[..],
+            "cyrene-1.0.0.tar.gz",
+            dest.path(),
+            &options,
+            "cyrene-1.0.0.tar.gz",
+            &Cancellation::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            std::fs::read(dest.path().join("bin/cyrene")).unwrap(),
+            binary
+        );
+        assert!(!dest.path().join("README.md").exists());
+
+        let members = vec!["cyrene-1.0.0/bin/cyrenectl".to_string()];
+        let options = ExtractOptions {
+            strip_components: 1,
+            members: &members,
+        };
+        let result = extract(
+            ArchiveFormat::TarGz,
+            &compressed[..],
+            "cyrene-1.0.0.tar.gz",
+            dest.path(),
+            &options,
+            "cyrene-1.0.0.tar.gz",
+            &Cancellation::default(),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(CyreneError::ArchiveMemberNotFound(member, _)) if member == "cyrene-1.0.0/bin/cyrenectl"
+        ));
+    }
+
+    /// Signing key and its armored public key
+    pub(crate) fn signing_key() -> (pgp::SignedSecretKey, String) {
+        use pgp::{ArmorOptions, KeyType, SecretKeyParamsBuilder, types::SecretKeyTrait};
+
+        let mut rng = rand::thread_rng();
+        let params = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::EdDSALegacy)
+            .can_certify(true)
+            .can_sign(true)
+            .primary_user_id("Cyrene <cyrene@example.com>".to_string())
+            .build()
+            .unwrap();
+        let secret_key = params
+            .generate(&mut rng)
+            .unwrap()
+            .sign(&mut rng, String::new)
+            .unwrap();
+        let public_key = secret_key
+            .public_key()
+            .sign(&mut rng, &secret_key, String::new)
+            .unwrap()
+            .to_armored_string(ArmorOptions::default())
+            .unwrap();
+
+        (secret_key, public_key)
+    }
+
+    /// Detached signature of `data` made with `secret_key`
+    pub(crate) fn sign(secret_key: &pgp::SignedSecretKey, data: &[u8]) -> StandaloneSignature {
+        use pgp::{Message, crypto::hash::HashAlgorithm};
+
+        Message::new_literal_bytes("", data)
+            .sign(
+                rand::thread_rng(),
+                secret_key,
+                String::new,
+                HashAlgorithm::SHA2_256,
+            )
+            .unwrap()
+            .into_signature()
+    }
+
+    #[tokio::test]
+    async fn test_verify_file_signature() {
+        use pgp::{ArmorOptions, ser::Serialize};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cyrene");
+        let data = b"#!/bin/sh\necho cyrene\n";
+        std::fs::write(&path, data).unwrap();
+        let (secret_key, public_key) = signing_key();
+        let signature = sign(&secret_key, data);
+
+        let binary = signature.to_bytes().unwrap();
+        verify_file_signature(&path, &binary, &public_key)
+            .await
+            .unwrap();
+
+        let armored = signature
+            .to_armored_string(ArmorOptions::default())
+            .unwrap();
+        verify_file_signature(&path, armored.as_bytes(), &public_key)
+            .await
+            .unwrap();
+
+        // Tampered data
+        std::fs::write(&path, b"#!/bin/sh\necho tampered\n").unwrap();
+        assert!(matches!(
+            verify_file_signature(&path, &binary, &public_key).await,
+            Err(CyreneError::SignatureVerify(..))
+        ));
+
+        // Signature made by another key
+        std::fs::write(&path, data).unwrap();
+        let (_, other_public_key) = signing_key();
+        assert!(matches!(
+            verify_file_signature(&path, &binary, &other_public_key).await,
+            Err(CyreneError::SignatureVerify(..))
+        ));
+    }
+
+    #[test]
+    fn test_verify_download() {
+        let dest = tempfile::tempdir().unwrap();
+        let path = dest.path().join("cyrene");
+        std::fs::write(&path, b"abc").unwrap();
+
+        verify_download(
+            &path,
+            "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD",
+        )
+        .unwrap();
+        assert!(matches!(
+            verify_download(&path, "0000"),
+            Err(CyreneError::ChecksumMismatch(..))
+        ));
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_decompress_exec() {
+        use async_compression::futures::bufread::GzipEncoder;
+        use futures::AsyncReadExt;
+        use std::os::unix::fs::PermissionsExt;
+
+        let binary = b"#!/bin/sh\necho cyrene\n";
+        let mut compressed = Vec::new();
+        GzipEncoder::new(&binary[..])
+            .read_to_end(&mut compressed)
+            .await
+            .unwrap();
+
+        let dest = tempfile::tempdir().unwrap();
+        let target_file = dest.path().join("cyrene");
+        decompress_exec(
+            &compressed[..],
+            ArchiveKind::Gzip,
+            &target_file,
+            "cyrene.gz",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&target_file).unwrap(), binary);
+        let mode = std::fs::metadata(&target_file)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
     }
 }
@@ -0,0 +1,50 @@
+use std::{path::Path, process::Command};
+
+use text_template::Template;
+
+use crate::{app::AppVerify, app_module::platform, errors::CyreneError};
+
+/// Run the verify command on `binary` and check its output
+pub fn process_verify(verify: &AppVerify, version: &str, binary: &Path) -> Result<(), CyreneError> {
+    let values = platform::template_values(version);
+    let args: Vec<String> = verify
+        .args
+        .iter()
+        .map(|f| Template::from(f.as_str()).fill_in(&values).to_string())
+        .collect();
+    let expect = Template::from(verify.expect.as_str())
+        .fill_in(&values)
+        .to_string();
+
+    let output = Command::new(binary)
+        .args(&args)
+        .output()
+        .map_err(|e| CyreneError::VerifyRun(binary.to_path_buf(), e))?;
+    // Some tools print their version to stderr
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() || !text.contains(&expect) {
+        return Err(CyreneError::VerifyFailed(binary.to_path_buf(), expect));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_output() {
+        let verify = AppVerify {
+            binary: "echo".to_string(),
+            args: vec!["echo ${version}".to_string()],
+            expect: "echo 1.2.3".to_string(),
+        };
+        assert!(process_verify(&verify, "1.2.3", Path::new("/bin/echo")).is_ok());
+        assert!(matches!(
+            process_verify(&verify, "2.0.0", Path::new("/bin/echo")),
+            Err(CyreneError::VerifyFailed(_, _))
+        ));
+    }
+}
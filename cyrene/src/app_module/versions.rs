@@ -1,76 +1,208 @@
+use std::collections::HashSet;
 
+use futures::StreamExt;
 use jsonpath_rust::JsonPath;
 use log::debug;
-use reqwest::header;
-use serde::Deserialize;
+use reqwest::{Response, header};
+use serde::{Deserialize, de::DeserializeOwned};
 use serde_json::Value;
 use url::Url;
 
 use crate::{
     app::{AppVersions, AppVersionsGithubCommand, AppVersionsUrlCommand},
     errors::CyreneError,
+    version::CyreneVersion,
 };
 
+/// A source of app versions
+pub trait VersionSource {
+    /// Fetch versions from the source, newest first
+    fn fetch(&self) -> impl Future<Output = Result<Vec<String>, CyreneError>>;
+}
+
 #[derive(Deserialize)]
 struct GitHubVersion {
     tag_name: String,
     prerelease: bool,
 }
 
-async fn process_github(
-    repo: &str,
-    command: &Option<Vec<AppVersionsGithubCommand>>,
-) -> Result<Vec<String>, CyreneError> {
-    let mut headers = header::HeaderMap::new();
-    headers.insert("Accept", "application/vnd.github+json".parse().unwrap());
-    headers.insert("X-GitHub-Api-Version", "2022-11-28".parse().unwrap());
-    headers.insert("User-Agent", "damillora-cyrene".parse().unwrap());
-    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
-        debug!("Found GitHub token");
-        headers.insert("Authorization", format!("Bearer {}", token).parse().unwrap());
-    }
-    debug!("Getting release info from {}", repo);
-    let mut versions: Vec<String> = Vec::new();
-    let mut still_more_stuff = true;
-    let mut page = 1;
-
-    while still_more_stuff && page <= 10 {
-        let client = reqwest::Client::new();
-        debug!(
-            "Calling https://api.github.com/repos/{}/releases?per_page=100&page={}",
-            repo, page
-        );
-        let url = format!(
-            "https://api.github.com/repos/{}/releases?per_page=100&page={}",
-            repo, page
+/// Versions from GitHub releases
+pub struct GithubVersionSource<'a> {
+    pub repo: &'a str,
+    pub command: &'a Option<Vec<AppVersionsGithubCommand>>,
+    pub max_response_size: u64,
+    pub client: &'a reqwest::Client,
+}
+
+/// Versions from a JSON document
+pub struct UrlVersionSource<'a> {
+    pub url: &'a Url,
+    pub command: &'a Vec<AppVersionsUrlCommand>,
+    pub max_response_size: u64,
+    pub client: &'a reqwest::Client,
+}
+
+/// Versions from the npm registry
+pub struct NpmVersionSource<'a> {
+    pub package: &'a str,
+    pub max_response_size: u64,
+    pub client: &'a reqwest::Client,
+}
+
+/// Read a JSON response, refusing to buffer more than `max_size` bytes
+async fn read_json<T: DeserializeOwned>(
+    res: Response,
+    url: &str,
+    max_size: u64,
+) -> Result<T, CyreneError> {
+    if let Some(len) = res.content_length()
+        && len > max_size
+    {
+        return Err(CyreneError::VersionResponseTooLarge(
+            url.to_string(),
+            max_size,
+        ));
+    }
+    let mut body: Vec<u8> = Vec::new();
+    let mut stream = res.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| CyreneError::VersionFetch(url.to_string(), e))?;
+        if (body.len() + chunk.len()) as u64 > max_size {
+            return Err(CyreneError::VersionResponseTooLarge(
+                url.to_string(),
+                max_size,
+            ));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    serde_json::from_slice(&body).map_err(|e| CyreneError::VersionParse(url.to_string(), e))
+}
+
+impl VersionSource for GithubVersionSource<'_> {
+    async fn fetch(&self) -> Result<Vec<String>, CyreneError> {
+        let repo = self.repo;
+        let mut headers = header::HeaderMap::new();
+        headers.insert("Accept", "application/vnd.github+json".parse().unwrap());
+        headers.insert("X-GitHub-Api-Version", "2022-11-28".parse().unwrap());
+        if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+            debug!("Found GitHub token");
+            headers.insert(
+                "Authorization",
+                format!("Bearer {}", token).parse().unwrap(),
+            );
+        }
+        debug!("Getting release info from {}", repo);
+        let mut versions: Vec<String> = Vec::new();
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut still_more_stuff = true;
+        let mut page = 1;
+
+        while still_more_stuff && page <= 10 {
+            debug!(
+                "Calling https://api.github.com/repos/{}/releases?per_page=100&page={}",
+                repo, page
+            );
+            let url = format!(
+                "https://api.github.com/repos/{}/releases?per_page=100&page={}",
+                repo, page
+            );
+            let res = self
+                .client
+                .get(&url)
+                .headers(headers.clone())
+                .send()
+                .await
+                .map_err(|e| CyreneError::VersionFetch(url.clone(), e))?;
+            let res = res
+                .error_for_status()
+                .map_err(|e| CyreneError::VersionFetch(url.clone(), e))?;
+            let a: Vec<GitHubVersion> = read_json(res, &url, self.max_response_size).await?;
+            if a.len() < 100 {
+                still_more_stuff = false;
+            }
+            if !append_new_tags(&mut versions, &mut seen, a) {
+                debug!("Page {} repeats releases, stopping", page);
+                still_more_stuff = false;
+            }
+            page += 1;
+        }
+
+        Ok(process_github_commands(versions, self.command))
+    }
+}
+
+impl VersionSource for UrlVersionSource<'_> {
+    async fn fetch(&self) -> Result<Vec<String>, CyreneError> {
+        let url = self.url;
+        debug!("Getting release info from {}", url);
+        debug!("Calling {}", url);
+        let res = self
+            .client
+            .get(url.to_string())
+            .send()
+            .await
+            .map_err(|e| CyreneError::VersionFetch(url.to_string(), e))?;
+        let result: Value = read_json(res, url.as_str(), self.max_response_size).await?;
+
+        process_url_commands(&result, self.command)
+    }
+}
+
+impl VersionSource for NpmVersionSource<'_> {
+    async fn fetch(&self) -> Result<Vec<String>, CyreneError> {
+        let url = format!("https://registry.npmjs.org/{}", self.package);
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            "Accept",
+            "application/vnd.npm.install-v1+json".parse().unwrap(),
         );
-        let res = client
+        debug!("Calling {}", url);
+        let res = self
+            .client
             .get(&url)
-            .headers(headers.clone())
+            .headers(headers)
             .send()
             .await
             .map_err(|e| CyreneError::VersionFetch(url.clone(), e))?;
         let res = res
             .error_for_status()
             .map_err(|e| CyreneError::VersionFetch(url.clone(), e))?;
-        let a: Vec<GitHubVersion> = res
-            .json()
-            .await
-            .map_err(|e| CyreneError::VersionFetch(url.clone(), e))?;
-        let mut a: Vec<String> = a
-            .iter()
-            .filter(|f| !f.prerelease)
-            .map(|f| {
-                debug!("found version: {}", f.tag_name);
-                f.tag_name.to_string()
-            })
-            .collect();
-        if a.len() < 100 {
-            still_more_stuff = false;
+        let result: Value = read_json(res, &url, self.max_response_size).await?;
+
+        Ok(process_npm_versions(&result))
+    }
+}
+
+/// Append non-prerelease tags of a page of releases to `versions`.
+///
+/// Returns false if the page only repeats releases already seen, as APIs ignoring the page
+/// parameter do. Pages overlapping by a few releases, e.g. when one was published meanwhile, still
+/// count as new.
+fn append_new_tags(
+    versions: &mut Vec<String>,
+    seen: &mut HashSet<String>,
+    page: Vec<GitHubVersion>,
+) -> bool {
+    let mut is_new = false;
+    for release in page {
+        if !seen.insert(release.tag_name.clone()) {
+            continue;
+        }
+        is_new = true;
+        if !release.prerelease {
+            debug!("found version: {}", release.tag_name);
+            versions.push(release.tag_name);
         }
-        versions.append(&mut a);
-        page += 1;
     }
+
+    is_new
+}
+
+fn process_github_commands(
+    mut versions: Vec<String>,
+    command: &Option<Vec<AppVersionsGithubCommand>>,
+) -> Vec<String> {
     if let Some(command) = command {
         for command in command {
             match command {
@@ -86,32 +218,13 @@ async fn process_github(
             }
         }
     }
-    // Automatically strip prefix in the end
-    if versions.iter().any(|e| e.starts_with("v")) {
-        versions = versions.iter().map(|e| e.strip_prefix("v").unwrap_or(e).to_string()).collect();
-    }
-    Ok(versions)
+    versions
 }
 
-async fn process_url(
-    url: &Url,
+fn process_url_commands(
+    result: &Value,
     command: &Vec<AppVersionsUrlCommand>,
 ) -> Result<Vec<String>, CyreneError> {
-    let mut headers = header::HeaderMap::new();
-    headers.insert("User-Agent", "damillora-cyrene".parse().unwrap());
-    debug!("Getting release info from {}", url);
-    let client = reqwest::Client::new();
-    debug!("Calling {}", url);
-    let res = client
-        .get(url.to_string())
-        .headers(headers.clone())
-        .send()
-        .await
-        .map_err(|e| CyreneError::VersionFetch(url.to_string(), e))?;
-    let result: Value = res
-        .json()
-        .await
-        .map_err(|e| CyreneError::VersionFetch(url.to_string(), e))?;
     let mut results: Vec<String> = Vec::new();
     for command in command {
         match command {
@@ -134,17 +247,79 @@ async fn process_url(
             }
         }
     }
+    Ok(results)
+}
+
+fn process_npm_versions(result: &Value) -> Vec<String> {
+    let mut versions: Vec<String> = result
+        .get("versions")
+        .and_then(|f| f.as_object())
+        .map(|f| {
+            f.keys()
+                .filter(|f| match CyreneVersion::parse(f) {
+                    CyreneVersion::Semver(ver) => ver.pre.is_empty(),
+                    CyreneVersion::NonSemver(_) => true,
+                })
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    versions.sort_by(|a, b| {
+        let a = CyreneVersion::parse(a);
+        let b = CyreneVersion::parse(b);
+        b.cmp(&a)
+    });
+    versions
+}
+
+/// Fetch versions from a source, stripping any `v` prefix and dropping duplicates
+pub async fn fetch_versions(source: &impl VersionSource) -> Result<Vec<String>, CyreneError> {
+    let mut versions = source.fetch().await?;
     // Automatically strip prefix in the end
-    if results.iter().any(|e| e.starts_with("v")) {
-        results = results.iter().map(|e| e.strip_prefix("v").unwrap_or(e).to_string()).collect();
+    if versions.iter().any(|e| e.starts_with("v")) {
+        versions = versions
+            .iter()
+            .map(|e| e.strip_prefix("v").unwrap_or(e).to_string())
+            .collect();
     }
-    Ok(results)
+    // Projects tagging both `v1.2.3` and `1.2.3` end up with the same version twice
+    let mut seen = HashSet::new();
+    versions.retain(|f| seen.insert(f.clone()));
+    Ok(versions)
 }
 
-pub async fn process_version(versions: &AppVersions) -> Result<Vec<String>, CyreneError> {
+pub async fn process_version(
+    versions: &AppVersions,
+    max_response_size: u64,
+    client: &reqwest::Client,
+) -> Result<Vec<String>, CyreneError> {
     match versions {
-        AppVersions::Github { repo, command } => process_github(repo, command).await,
-        AppVersions::Url { url, command } => process_url(url, command).await,
+        AppVersions::Github { repo, command } => {
+            fetch_versions(&GithubVersionSource {
+                repo,
+                command,
+                max_response_size,
+                client,
+            })
+            .await
+        }
+        AppVersions::Url { url, command } => {
+            fetch_versions(&UrlVersionSource {
+                url,
+                command,
+                max_response_size,
+                client,
+            })
+            .await
+        }
+        AppVersions::Npm { package } => {
+            fetch_versions(&NpmVersionSource {
+                package,
+                max_response_size,
+                client,
+            })
+            .await
+        }
     }
 }
 
@@ -152,7 +327,158 @@ pub async fn process_version(versions: &AppVersions) -> Result<Vec<String>, Cyre
 mod tests {
     use std::str::FromStr;
 
+    use serde_json::json;
+
     use super::*;
+    use crate::{
+        config::{CyreneConfig, DEFAULT_MAX_RESPONSE_SIZE},
+        http::build_http_client,
+    };
+
+    struct MockVersionSource(Vec<&'static str>);
+
+    impl VersionSource for MockVersionSource {
+        async fn fetch(&self) -> Result<Vec<String>, CyreneError> {
+            Ok(self.0.iter().map(|f| f.to_string()).collect())
+        }
+    }
+
+    /// Serve `head` and `body` to a single request, returning the URL to request
+    fn serve_once(head: &'static str, body: &'static str) -> String {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            // The client may hang up once it has seen enough
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(body.as_bytes());
+        });
+
+        format!("http://127.0.0.1:{port}/versions.json")
+    }
+
+    #[tokio::test]
+    async fn test_read_json_too_large() {
+        let body = r#"["1.0.0", "2.0.0", "3.0.0"]"#;
+        let client = reqwest::Client::new();
+        let heads = [
+            // Rejected from the Content-Length header
+            "HTTP/1.1 200 OK\r\nContent-Length: 27\r\nConnection: close\r\n\r\n",
+            // Rejected while streaming the body
+            "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n",
+        ];
+        for head in heads {
+            let url = serve_once(head, body);
+            let res = client.get(&url).send().await.unwrap();
+            let result: Result<Vec<String>, _> = read_json(res, &url, 10).await;
+            assert!(matches!(
+                result,
+                Err(CyreneError::VersionResponseTooLarge(_, 10))
+            ));
+        }
+
+        let url = serve_once(heads[1], body);
+        let res = client.get(&url).send().await.unwrap();
+        let result: Vec<String> = read_json(res, &url, 27).await.unwrap();
+        assert_eq!(result, vec!["1.0.0", "2.0.0", "3.0.0"]);
+    }
+
+    #[tokio::test]
+    async fn test_mock() {
+        let source = MockVersionSource(vec!["v1.1.0", "v1.0.0", "0.9.0"]);
+        let result = fetch_versions(&source).await.unwrap();
+
+        assert_eq!(result, vec!["1.1.0", "1.0.0", "0.9.0"]);
+    }
+
+    #[tokio::test]
+    async fn test_mixed_prefix_duplicates() {
+        let source = MockVersionSource(vec!["v1.2.3", "1.2.3", "v1.2.2", "1.2.1", "1.2.2"]);
+        let result = fetch_versions(&source).await.unwrap();
+
+        assert_eq!(result, vec!["1.2.3", "1.2.2", "1.2.1"]);
+    }
+
+    #[test]
+    fn test_append_new_tags() {
+        let release = |tag_name: &str, prerelease: bool| GitHubVersion {
+            tag_name: tag_name.to_string(),
+            prerelease,
+        };
+        let mut versions = Vec::new();
+        let mut seen = HashSet::new();
+
+        let page = vec![release("v2.0.0-rc.1", true), release("v1.1.0", false)];
+        assert!(append_new_tags(&mut versions, &mut seen, page));
+        let page = vec![release("v1.0.0", false)];
+        assert!(append_new_tags(&mut versions, &mut seen, page));
+        // A page overlapping the previous one by a release
+        let page = vec![release("v1.0.0", false), release("v0.9.0", false)];
+        assert!(append_new_tags(&mut versions, &mut seen, page));
+        // A repeated page
+        let page = vec![release("v1.0.0", false), release("v0.9.0", false)];
+        assert!(!append_new_tags(&mut versions, &mut seen, page));
+
+        assert_eq!(versions, vec!["v1.1.0", "v1.0.0", "v0.9.0"]);
+    }
+
+    #[test]
+    fn test_github_commands() {
+        let versions = vec!["release-1_1".to_string(), "release-1_0".to_string()];
+        let command = Some(vec![
+            AppVersionsGithubCommand::StripPrefix {
+                prefix: "release-".to_string(),
+            },
+            AppVersionsGithubCommand::Replace {
+                str: "_".to_string(),
+                with: ".".to_string(),
+            },
+        ]);
+        let result = process_github_commands(versions, &command);
+
+        assert_eq!(result, vec!["1.1", "1.0"]);
+    }
+
+    #[test]
+    fn test_url_commands() {
+        let value = json!([{ "version": "v22.0.0" }, { "version": "v20.0.0" }]);
+        let command = vec![
+            AppVersionsUrlCommand::Jsonpath {
+                query: "$[*].version".to_string(),
+            },
+            AppVersionsUrlCommand::StripPrefix {
+                prefix: "v".to_string(),
+            },
+        ];
+        let result = process_url_commands(&value, &command).unwrap();
+
+        assert_eq!(result, vec!["22.0.0", "20.0.0"]);
+    }
+
+    #[test]
+    fn test_npm_versions() {
+        let value = json!({
+            "versions": {
+                "1.0.0": {},
+                "1.2.0": {},
+                "1.10.0": {},
+                "2.0.0-beta.1": {}
+            }
+        });
+        let result = process_npm_versions(&value);
+
+        assert_eq!(result, vec!["1.10.0", "1.2.0", "1.0.0"]);
+    }
 
     #[tokio::test]
     async fn test_github() {
@@ -160,7 +486,12 @@ mod tests {
             repo: "Damillora/cyrene".to_string(),
             command: None,
         };
-        let result = process_version(&version).await;
+        let result = process_version(
+            &version,
+            DEFAULT_MAX_RESPONSE_SIZE,
+            &build_http_client(&CyreneConfig::default()).unwrap(),
+        )
+        .await;
 
         if let Ok(result) = result {
             assert!(result.len() > 0);
@@ -180,7 +511,12 @@ mod tests {
             }],
         };
 
-        let result = process_version(&version).await;
+        let result = process_version(
+            &version,
+            DEFAULT_MAX_RESPONSE_SIZE,
+            &build_http_client(&CyreneConfig::default()).unwrap(),
+        )
+        .await;
 
         if let Ok(result) = result {
             assert!(result.len() > 0);
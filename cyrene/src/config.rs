@@ -1,31 +1,98 @@
-use std::{fs, path::{Path, PathBuf}};
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
 
+use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 
 use crate::errors::CyreneError;
 
 
-#[derive(Serialize, Deserialize)]
+/// Default maximum size of responses when fetching versions
+pub const DEFAULT_MAX_RESPONSE_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Layout of installed versions in the apps directory
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppsLayout {
+    /// `apps_dir/name/version`
+    #[default]
+    Nested,
+    /// `apps_dir/name@version`
+    Flat,
+}
+
+/// How binaries are placed in `install_dir`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+    #[default]
+    Symlink,
+    Hardlink,
+    Copy,
+}
+
+/// Version part used for version-suffixed links to binaries
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionSuffix {
+    /// `node-20`, falling back to the full version for non-semver versions
+    Major,
+    /// `node-20.11.1`
+    Full,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct CyreneConfig {
     pub apps_dir: Option<PathBuf>,
     pub plugins_dir: Option<PathBuf>,
+    /// Additional plugin directories, searched after `plugins_dir`
+    pub plugin_paths: Option<Vec<PathBuf>>,
     pub install_dir: Option<PathBuf>,
     pub cache_dir: Option<PathBuf>,
     pub lockfile_path: Option<PathBuf>,
+    /// Number of previous versions kept when upgrading
+    pub keep_on_upgrade: Option<usize>,
+    /// Timeout in seconds for fetching versions and installing apps
+    pub plugin_timeout: Option<u64>,
+    /// Timeout in seconds for installing an app, including post-install
+    pub install_timeout: Option<u64>,
+    /// Maximum size in bytes of responses when fetching versions
+    pub max_response_size: Option<u64>,
+    /// Layout of installed versions in `apps_dir`
+    pub layout: Option<AppsLayout>,
+    /// URL of a plugin registry index
+    pub registry_url: Option<String>,
+    /// Link binaries through shims that pick the version from the nearest `cyrene.lock`
+    pub shims: Option<bool>,
+    /// How binaries are linked into `install_dir`
+    pub link_mode: Option<LinkMode>,
+    /// Mode used when hardlinking fails because the binary is on another filesystem
+    pub link_fallback: Option<LinkMode>,
+    /// Also link binaries under a version-suffixed name, so several versions can be on PATH
+    pub version_suffix: Option<VersionSuffix>,
+    /// Proxy URL used for all HTTP requests
+    pub proxy: Option<String>,
+    /// PEM bundle of additional CA certificates trusted for HTTPS
+    pub ca_bundle: Option<PathBuf>,
+    /// Timeout in seconds for each HTTP request
+    pub http_timeout: Option<u64>,
+    /// Directories binaries of specific apps are linked into instead of `install_dir`
+    pub bin_overrides: Option<BTreeMap<String, PathBuf>>,
+    /// Answer used when confirmation prompts are answered with Enter
+    pub default_confirm: Option<bool>,
 }
 
 impl CyreneConfig {
+    pub fn default_path() -> PathBuf {
+        let proj_dirs = ProjectDirs::from("com", "Damillora", "Cyrene").unwrap();
+        let mut config_path = proj_dirs.config_dir().to_path_buf();
+        config_path.push("cyrene.toml");
+
+        config_path
+    }
     pub fn load(config_path: &Path) -> Result<CyreneConfig, CyreneError> {
         let config = if !fs::exists(config_path).map_err(CyreneError::ConfigRead)? {
-            let config = CyreneConfig {
-                apps_dir: None,
-                plugins_dir: None,
-                install_dir: None,
-                cache_dir: None,
-                lockfile_path: None,
-            };
-            let config_toml = toml::ser::to_string(&config).map_err(CyreneError::ConfigSerialize)?;
-            fs::write(config_path, config_toml).map_err(CyreneError::ConfigWrite)?;
+            let config = CyreneConfig::default();
+            config.save(config_path)?;
 
             config
         } else {
@@ -38,4 +105,40 @@ impl CyreneConfig {
 
         Ok(config)
     }
-}
\ No newline at end of file
+
+    pub fn save(&self, config_path: &Path) -> Result<(), CyreneError> {
+        let config_toml = toml::ser::to_string(self).map_err(CyreneError::ConfigSerialize)?;
+        fs::write(config_path, config_toml).map_err(CyreneError::ConfigWrite)
+    }
+
+    pub fn keep_on_upgrade(&self) -> usize {
+        self.keep_on_upgrade.unwrap_or(0)
+    }
+
+    pub fn default_confirm(&self) -> bool {
+        self.default_confirm.unwrap_or(false)
+    }
+
+    pub fn shims(&self) -> bool {
+        self.shims.unwrap_or(false)
+    }
+
+    pub fn link_mode(&self) -> LinkMode {
+        self.link_mode.unwrap_or_default()
+    }
+
+    pub fn link_fallback(&self) -> LinkMode {
+        self.link_fallback.unwrap_or_default()
+    }
+
+    pub fn bin_override(&self, name: &str) -> Option<&Path> {
+        self.bin_overrides
+            .as_ref()
+            .and_then(|f| f.get(name))
+            .map(|f| f.as_path())
+    }
+
+    pub fn max_response_size(&self) -> u64 {
+        self.max_response_size.unwrap_or(DEFAULT_MAX_RESPONSE_SIZE)
+    }
+}
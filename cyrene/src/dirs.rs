@@ -1,17 +1,26 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
 
 use directories::ProjectDirs;
 use log::debug;
 
-use crate::{config::CyreneConfig, errors::CyreneError};
+use crate::{
+    config::{AppsLayout, CyreneConfig},
+    errors::CyreneError,
+};
 
 pub struct CyreneDirs {
     pub apps_dir: PathBuf,
     pub plugins_dir: PathBuf,
+    pub plugin_paths: Vec<PathBuf>,
     pub exe_dir: PathBuf,
     pub config_dir: PathBuf,
     pub cache_dir: PathBuf,
     pub version_cache_path: PathBuf,
+    pub registry_index_path: PathBuf,
+    pub layout: AppsLayout,
     lockfile_path: PathBuf,
 }
 impl CyreneDirs {
@@ -35,16 +44,186 @@ impl CyreneDirs {
         Ok(())
     }
     pub fn installation_path(&self, name: &str, version: &str) -> PathBuf {
+        self.installation_path_in(self.layout, name, version)
+    }
+    fn installation_path_in(&self, layout: AppsLayout, name: &str, version: &str) -> PathBuf {
         let mut installation_dir = self.apps_dir.clone();
-        installation_dir.push(format!("{}/{}", name, version));
+        match layout {
+            AppsLayout::Nested => installation_dir.push(format!("{}/{}", name, version)),
+            AppsLayout::Flat => installation_dir.push(format!("{}@{}", name, version)),
+        }
 
         installation_dir
     }
-    pub fn installation_root(&self, name: &str) -> PathBuf {
-        let mut installation_dir = self.apps_dir.clone();
-        installation_dir.push(name);
+    /// Directory holding all versions of an app, if the layout has one
+    pub fn installation_root(&self, name: &str) -> Option<PathBuf> {
+        match self.layout {
+            AppsLayout::Nested => {
+                let mut installation_dir = self.apps_dir.clone();
+                installation_dir.push(name);
 
-        installation_dir
+                Some(installation_dir)
+            }
+            AppsLayout::Flat => None,
+        }
+    }
+    /// Names of apps with installed versions
+    pub fn installed_apps(&self) -> io::Result<Vec<String>> {
+        self.installed_apps_in(self.layout)
+    }
+    fn installed_apps_in(&self, layout: AppsLayout) -> io::Result<Vec<String>> {
+        let mut apps: Vec<String> = Vec::new();
+        for entry in read_dir_names(&self.apps_dir)? {
+            let name = match layout {
+                // An empty installation root is left behind by a failed first install
+                AppsLayout::Nested if !entry.contains('@') => {
+                    if self.installed_versions_in(layout, &entry)?.is_empty() {
+                        continue;
+                    }
+                    entry
+                }
+                AppsLayout::Flat => match entry.split_once('@') {
+                    Some((name, _)) => name.to_string(),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if !apps.contains(&name) {
+                apps.push(name);
+            }
+        }
+
+        Ok(apps)
+    }
+    /// Installed versions of an app, in no particular order
+    pub fn installed_versions(&self, name: &str) -> io::Result<Vec<String>> {
+        self.installed_versions_in(self.layout, name)
+    }
+    fn installed_versions_in(&self, layout: AppsLayout, name: &str) -> io::Result<Vec<String>> {
+        match layout {
+            AppsLayout::Nested => {
+                let mut installation_root = self.apps_dir.clone();
+                installation_root.push(name);
+                read_dir_names(&installation_root)
+            }
+            AppsLayout::Flat => Ok(read_dir_names(&self.apps_dir)?
+                .iter()
+                .filter_map(|f| f.split_once('@'))
+                .filter(|(app, _)| app.eq(&name))
+                .map(|(_, version)| version.to_string())
+                .collect()),
+        }
+    }
+    /// App and version of the installation containing `path`
+    pub fn find_installation(&self, path: &Path) -> Option<(String, String)> {
+        let mut components = path.strip_prefix(&self.apps_dir).ok()?.components();
+        let first = components.next()?.as_os_str().to_string_lossy().to_string();
+        // Recognize both layouts, in case installs have not been migrated yet
+        match first.split_once('@') {
+            Some((name, version)) => Some((name.to_string(), version.to_string())),
+            None => {
+                let version = components.next()?.as_os_str().to_string_lossy().to_string();
+                Some((first, version))
+            }
+        }
+    }
+    /// Move installs from the other layout into the configured one
+    pub fn migrate_layout(&self) -> io::Result<Vec<(String, String)>> {
+        let from = match self.layout {
+            AppsLayout::Nested => AppsLayout::Flat,
+            AppsLayout::Flat => AppsLayout::Nested,
+        };
+        let mut migrated = Vec::new();
+        for name in self.installed_apps_in(from)? {
+            for version in self.installed_versions_in(from, &name)? {
+                let source = self.installation_path_in(from, &name, &version);
+                let dest = self.installation_path(&name, &version);
+                debug!("Moving {} to {}", source.display(), dest.display());
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(&source, &dest)?;
+                migrated.push((name.clone(), version));
+            }
+            if from == AppsLayout::Nested {
+                let mut installation_root = self.apps_dir.clone();
+                installation_root.push(&name);
+                fs::remove_dir(&installation_root)?;
+            }
+        }
+
+        Ok(migrated)
+    }
+    /// Remove installation roots without any versions, returning the apps they belonged to
+    pub fn remove_empty_installation_roots(&self) -> io::Result<Vec<String>> {
+        if self.layout != AppsLayout::Nested {
+            return Ok(Vec::new());
+        }
+        let mut removed = Vec::new();
+        for entry in read_dir_names(&self.apps_dir)? {
+            let mut installation_root = self.apps_dir.clone();
+            installation_root.push(&entry);
+            if entry.contains('@')
+                || !installation_root.is_dir()
+                || !read_dir_names(&installation_root)?.is_empty()
+            {
+                continue;
+            }
+            debug!("Removing empty {}", installation_root.display());
+            fs::remove_dir(&installation_root)?;
+            removed.push(entry);
+        }
+
+        Ok(removed)
+    }
+    /// Size in bytes of an installed version
+    pub fn installation_size(&self, name: &str, version: &str) -> io::Result<u64> {
+        dir_size(&self.installation_path(name, version))
+    }
+    /// Name of the plugin matching `name` regardless of case, as spelled by its file name.
+    ///
+    /// Plugin files are matched by listing the plugin directories, so the result is the same on
+    /// case-sensitive and case-insensitive filesystems. An exact match is preferred.
+    pub fn find_plugin_name(&self, name: &str) -> Option<String> {
+        let file_name = format!("{}.cyrene", name);
+        let mut found = None;
+        for plugin_path in &self.plugin_paths {
+            let Ok(entries) = fs::read_dir(plugin_path) else {
+                continue;
+            };
+            for entry in entries.filter_map(|f| f.ok()) {
+                let entry_name = entry.file_name().to_string_lossy().to_string();
+                if entry_name.eq(&file_name) {
+                    return Some(name.to_string());
+                }
+                if found.is_none() && entry_name.eq_ignore_ascii_case(&file_name) {
+                    found = entry_name.strip_suffix(".cyrene").map(String::from);
+                }
+            }
+        }
+
+        found
+    }
+    /// Plugin files in all plugin directories, including shadowed ones
+    pub fn plugin_files(&self) -> io::Result<Vec<PathBuf>> {
+        let mut plugin_files = Vec::new();
+        for plugin_path in &self.plugin_paths {
+            let mut names: Vec<String> = read_dir_names(plugin_path)?
+                .into_iter()
+                .filter(|f| f.ends_with(".cyrene"))
+                .collect();
+            names.sort();
+            plugin_files.extend(names.into_iter().map(|f| plugin_path.join(f)));
+        }
+
+        Ok(plugin_files)
+    }
+    /// Use another plugins directory in place of the configured one
+    pub fn override_plugins_dir(&mut self, plugins_dir: PathBuf) {
+        if let Some(first) = self.plugin_paths.first_mut() {
+            *first = plugins_dir.clone();
+        }
+        self.plugins_dir = plugins_dir;
     }
     pub fn lockfile_path(&self) -> PathBuf {
         self.lockfile_path.clone()
@@ -64,19 +243,74 @@ impl CyreneDirs {
         Ok(installation_path)
     }
 }
+fn read_dir_names(path: &Path) -> io::Result<Vec<String>> {
+    let list_dirs = match fs::read_dir(path) {
+        Ok(list_dirs) => list_dirs,
+        // Nothing has been installed yet
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    Ok(list_dirs
+        .filter_map(|p| p.ok())
+        .map(|p| p.file_name().to_string_lossy().to_string())
+        .collect())
+}
+/// Total size of files under `path`.
+///
+/// Symlinks are counted by their own size and never followed, so link cycles cannot loop forever.
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.path().symlink_metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+
+    Ok(size)
+}
+/// Absolute form of `path`, with symlinks resolved if it exists and `..` removed otherwise
+fn resolve_path(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => resolved.push(component),
+        }
+    }
+
+    resolved
+}
+/// Reject a binaries directory that is inside the apps directory or contains it
+pub fn check_not_nested(exe_dir: &Path, apps_dir: &Path) -> Result<(), CyreneError> {
+    let (exe_dir, apps_dir) = (resolve_path(exe_dir), resolve_path(apps_dir));
+    if exe_dir.starts_with(&apps_dir) {
+        return Err(CyreneError::NestedDirs(exe_dir, apps_dir));
+    }
+    if apps_dir.starts_with(&exe_dir) {
+        return Err(CyreneError::NestedDirs(apps_dir, exe_dir));
+    }
+
+    Ok(())
+}
 impl CyreneDirs {
-    pub fn new(config_path: &Option<String>) -> Result<Self, CyreneError> {
+    pub fn new(config: &CyreneConfig) -> Result<Self, CyreneError> {
         let proj_dirs = ProjectDirs::from("com", "Damillora", "Cyrene").unwrap();
 
-        let config_path = if let Some(conf) = config_path {
-            PathBuf::from(conf)
-        } else {
-            let mut config_path = proj_dirs.config_dir().to_path_buf();
-            config_path.push("cyrene.toml");
-
-            config_path
-        };
-        let config = CyreneConfig::load(&config_path)?;
         let apps_dir = match std::env::var("CYRENE_APPS_DIR") {
             Ok(env) => PathBuf::from(env),
             Err(_) => {
@@ -103,6 +337,10 @@ impl CyreneDirs {
                 }
             }
         };
+        let mut plugin_paths = vec![plugins_dir.clone()];
+        if let Some(extra_plugin_paths) = &config.plugin_paths {
+            plugin_paths.extend(extra_plugin_paths.iter().cloned());
+        }
         let config_dir = proj_dirs.config_dir().to_path_buf();
         let exe_dir = match std::env::var("CYRENE_INSTALL_DIR") {
             Ok(env) => PathBuf::from(env),
@@ -125,6 +363,8 @@ impl CyreneDirs {
         };
         let mut versions_cache_dir = cache_dir.clone();
         versions_cache_dir.push("versions.yaml");
+        let mut registry_index_path = cache_dir.clone();
+        registry_index_path.push("registry.toml");
         let lockfile_path = if let Some(lockfile_path) = &config.lockfile_path {
             lockfile_path.clone()
         } else {
@@ -133,14 +373,201 @@ impl CyreneDirs {
 
             lockfile_path
         };
+        check_not_nested(&exe_dir, &apps_dir)?;
+        for bin_override in config.bin_overrides.iter().flatten() {
+            check_not_nested(bin_override.1, &apps_dir)?;
+        }
         Ok(Self {
             apps_dir,
             plugins_dir,
+            plugin_paths,
             config_dir,
             exe_dir,
             cache_dir,
             version_cache_path: versions_cache_dir,
+            registry_index_path,
+            layout: config.layout.unwrap_or_default(),
             lockfile_path,
         })
     }
 }
+
+#[cfg(test)]
+impl CyreneDirs {
+    /// Directories under `root`, for tests
+    pub fn in_root(root: &Path) -> Self {
+        let cache_dir = root.join("cache");
+        Self {
+            apps_dir: root.join("apps"),
+            plugins_dir: root.join("plugins"),
+            plugin_paths: vec![root.join("plugins")],
+            exe_dir: root.join("bin"),
+            config_dir: root.join("config"),
+            version_cache_path: cache_dir.join("versions.toml"),
+            registry_index_path: cache_dir.join("registry.toml"),
+            cache_dir,
+            layout: AppsLayout::Nested,
+            lockfile_path: root.join("config/cyrene.toml"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dirs(apps_dir: &Path, layout: AppsLayout) -> CyreneDirs {
+        CyreneDirs {
+            apps_dir: apps_dir.to_path_buf(),
+            plugins_dir: PathBuf::new(),
+            plugin_paths: Vec::new(),
+            exe_dir: PathBuf::new(),
+            config_dir: PathBuf::new(),
+            cache_dir: PathBuf::new(),
+            version_cache_path: PathBuf::new(),
+            registry_index_path: PathBuf::new(),
+            layout,
+            lockfile_path: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn test_migrate_layout() {
+        let apps_dir = tempfile::tempdir().unwrap();
+        let nested = test_dirs(apps_dir.path(), AppsLayout::Nested);
+        nested.ensure_installation_dir("node", "22.0.0").unwrap();
+        nested.ensure_installation_dir("node", "20.0.0").unwrap();
+        nested
+            .ensure_installation_dir("rust-analyzer", "2025-01-01")
+            .unwrap();
+
+        let flat = test_dirs(apps_dir.path(), AppsLayout::Flat);
+        assert!(flat.installed_apps().unwrap().is_empty());
+        assert_eq!(flat.migrate_layout().unwrap().len(), 3);
+
+        let mut apps = flat.installed_apps().unwrap();
+        apps.sort();
+        assert_eq!(apps, vec!["node", "rust-analyzer"]);
+        let mut versions = flat.installed_versions("node").unwrap();
+        versions.sort();
+        assert_eq!(versions, vec!["20.0.0", "22.0.0"]);
+        assert!(nested.installed_apps().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_empty_installation_root() {
+        let apps_dir = tempfile::tempdir().unwrap();
+        let dirs = test_dirs(apps_dir.path(), AppsLayout::Nested);
+        dirs.ensure_installation_dir("node", "22.0.0").unwrap();
+        fs::create_dir_all(apps_dir.path().join("deno")).unwrap();
+
+        assert_eq!(dirs.installed_apps().unwrap(), vec!["node"]);
+        assert_eq!(
+            dirs.remove_empty_installation_roots().unwrap(),
+            vec!["deno"]
+        );
+        assert!(!apps_dir.path().join("deno").exists());
+        assert!(apps_dir.path().join("node").exists());
+    }
+
+    #[test]
+    fn test_find_plugin_name() {
+        let plugins_dir = tempfile::tempdir().unwrap();
+        let extra_plugins_dir = tempfile::tempdir().unwrap();
+        fs::write(plugins_dir.path().join("node.cyrene"), "").unwrap();
+        fs::write(plugins_dir.path().join("Deno.cyrene"), "").unwrap();
+        fs::write(extra_plugins_dir.path().join("deno.cyrene"), "").unwrap();
+        let mut dirs = test_dirs(Path::new("/apps"), AppsLayout::Nested);
+        dirs.plugin_paths = vec![
+            plugins_dir.path().to_path_buf(),
+            extra_plugins_dir.path().to_path_buf(),
+        ];
+
+        assert_eq!(dirs.find_plugin_name("node"), Some("node".to_string()));
+        assert_eq!(dirs.find_plugin_name("Node"), Some("node".to_string()));
+        assert_eq!(dirs.find_plugin_name("NODE"), Some("node".to_string()));
+        // An exact match in a later directory wins over a case-insensitive one
+        assert_eq!(dirs.find_plugin_name("deno"), Some("deno".to_string()));
+        assert_eq!(dirs.find_plugin_name("go"), None);
+    }
+
+    #[test]
+    fn test_plugin_files() {
+        let plugins_dir = tempfile::tempdir().unwrap();
+        fs::write(plugins_dir.path().join("node.cyrene"), "").unwrap();
+        fs::write(plugins_dir.path().join("deno.cyrene"), "").unwrap();
+        fs::write(plugins_dir.path().join("README.md"), "").unwrap();
+        let mut dirs = test_dirs(Path::new("/apps"), AppsLayout::Nested);
+        dirs.plugin_paths = vec![
+            plugins_dir.path().to_path_buf(),
+            plugins_dir.path().join("missing"),
+        ];
+
+        assert_eq!(
+            dirs.plugin_files().unwrap(),
+            vec![
+                plugins_dir.path().join("deno.cyrene"),
+                plugins_dir.path().join("node.cyrene"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_not_nested() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps_dir = dir.path().join("apps");
+        fs::create_dir_all(&apps_dir).unwrap();
+
+        assert!(check_not_nested(&dir.path().join("bin"), &apps_dir).is_ok());
+        assert!(check_not_nested(&dir.path().join("apps-bin"), &apps_dir).is_ok());
+        assert!(matches!(
+            check_not_nested(&apps_dir.join("bin"), &apps_dir),
+            Err(CyreneError::NestedDirs(_, _))
+        ));
+        assert!(matches!(
+            check_not_nested(dir.path(), &apps_dir),
+            Err(CyreneError::NestedDirs(_, _))
+        ));
+        assert!(matches!(
+            check_not_nested(&apps_dir, &apps_dir),
+            Err(CyreneError::NestedDirs(_, _))
+        ));
+        // Relative components are resolved before comparing
+        assert!(matches!(
+            check_not_nested(&dir.path().join("bin/../apps/bin"), &apps_dir),
+            Err(CyreneError::NestedDirs(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_find_installation() {
+        let dirs = test_dirs(Path::new("/apps"), AppsLayout::Nested);
+
+        assert_eq!(
+            dirs.find_installation(Path::new("/apps/node/22.0.0/bin/node")),
+            Some(("node".to_string(), "22.0.0".to_string()))
+        );
+        assert_eq!(
+            dirs.find_installation(Path::new("/apps/node@22.0.0/bin/node")),
+            Some(("node".to_string(), "22.0.0".to_string()))
+        );
+        assert_eq!(dirs.find_installation(Path::new("/usr/bin/node")), None);
+    }
+
+    #[test]
+    fn test_installation_size() {
+        let apps_dir = tempfile::tempdir().unwrap();
+        let dirs = test_dirs(apps_dir.path(), AppsLayout::Nested);
+        let installation_path = dirs.ensure_installation_dir("node", "22.0.0").unwrap();
+        let bin_dir = installation_path.join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(installation_path.join("README.md"), [0; 100]).unwrap();
+        fs::write(bin_dir.join("node"), [0; 1000]).unwrap();
+        // A link back to the installation must not be followed
+        symlink::symlink_dir(&installation_path, bin_dir.join("loop")).unwrap();
+
+        let size = dirs.installation_size("node", "22.0.0").unwrap();
+        assert!(size >= 1100);
+        assert!(size < 1100 + 4096);
+    }
+}
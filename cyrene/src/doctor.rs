@@ -0,0 +1,64 @@
+use std::{fmt, path::PathBuf};
+
+use serde::Serialize;
+
+/// A problem found by `cyrene doctor`
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "category", rename_all = "snake_case")]
+pub enum CyreneIssue {
+    /// A linked binary points to a file that no longer exists
+    DanglingSymlink { binary: String, target: PathBuf },
+    /// An installed app without a plugin
+    OrphanApp { app: String },
+    /// A lockfile version that is not installed
+    MissingInstall { app: String, version: String },
+    /// A version cache that cannot be parsed
+    CorruptCache { path: PathBuf, error: String },
+}
+
+impl fmt::Display for CyreneIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CyreneIssue::DanglingSymlink { binary, target } => {
+                write!(f, "{} points to missing {}", binary, target.display())
+            }
+            CyreneIssue::OrphanApp { app } => {
+                write!(f, "{} is installed but has no plugin", app)
+            }
+            CyreneIssue::MissingInstall { app, version } => write!(
+                f,
+                "{} version {} is in the lockfile but not installed",
+                app, version
+            ),
+            CyreneIssue::CorruptCache { path, error } => {
+                write!(f, "version cache {} is corrupt: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_json() {
+        let issue = CyreneIssue::MissingInstall {
+            app: "node".to_string(),
+            version: "22.0.0".to_string(),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&issue).unwrap(),
+            serde_json::json!({
+                "category": "missing_install",
+                "app": "node",
+                "version": "22.0.0",
+            })
+        );
+        assert_eq!(
+            issue.to_string(),
+            "node version 22.0.0 is in the lockfile but not installed"
+        );
+    }
+}
@@ -9,8 +9,6 @@ pub enum CyreneError {
     VersionCacheRead(std::io::Error),
     #[error("Unable to write from version cache: {0}")]
     VersionCacheWrite(std::io::Error),
-    #[error("Unable to deserialize version cache: {0}")]
-    VersionCacheDeserialize(toml::de::Error),
     #[error("Unable to serialize version cache: {0}")]
     VersionCacheSerialize(toml::ser::Error),
     #[error("Unable to read lockfile: {0}")]
@@ -25,20 +23,58 @@ pub enum CyreneError {
     LockfileLocalRead(PathBuf, std::io::Error),
     #[error("Unable to initialize directory {0}: {1}")]
     DirectoryInit(PathBuf, std::io::Error),
+    #[error("{0} is inside {1}, install_dir and apps_dir must not contain each other")]
+    NestedDirs(PathBuf, PathBuf),
     #[error("Unable to parse app: {0}")]
     AppDeserialize(toml::de::Error),
     #[error("Unable to read app from {0}: {1}")]
     AppRead(PathBuf, std::io::Error),
+    #[error("Invalid HTTP header {0} in app")]
+    HttpHeader(String),
+    #[error("Unable to build HTTP client: {0}")]
+    HttpClient(reqwest::Error),
+    #[error("Invalid proxy {0}: {1}")]
+    HttpProxy(String, reqwest::Error),
+    #[error("Unable to read CA bundle from {0}: {1}")]
+    CaBundleRead(PathBuf, std::io::Error),
+    #[error("Unable to parse CA bundle from {0}: {1}")]
+    CaBundleParse(PathBuf, reqwest::Error),
+    #[error("Binary {0} to verify is not declared in the app")]
+    VerifyBinaryNotFound(String),
+    #[error("The app does not define any actions")]
+    AppNoActions,
+    #[error("Unknown action {0}, available actions: {1}")]
+    AppActionNotFound(String, String),
+    #[error("Binary {0} for action {1} is not declared in the app")]
+    ActionBinaryNotFound(String, String),
+    #[error("Unable to run {0} to verify the installation: {1}")]
+    VerifyRun(PathBuf, std::io::Error),
+    #[error("Verification of {0} failed: output does not contain {1}")]
+    VerifyFailed(PathBuf, String),
     #[error("Unable to fetch version info from {0}: {1}")]
     VersionFetch(String, reqwest::Error),
+    #[error("Response from {0} is larger than {1} bytes")]
+    VersionResponseTooLarge(String, u64),
+    #[error("Unable to parse version info from {0}: {1}")]
+    VersionParse(String, serde_json::Error),
     #[error("Unable to execute JSON query {0}: {1}")]
     VersionQueryParse(String, jsonpath_rust::parser::errors::JsonPathError),
     #[error("Unable to list apps in {0}: {1}")]
     AppList(PathBuf, std::io::Error),
+    #[error("Unable to remove empty installation roots in {0}: {1}")]
+    AppRootRemove(PathBuf, std::io::Error),
+    #[error("Unable to migrate apps to the configured layout: {0}")]
+    LayoutMigrate(std::io::Error),
     #[error("Unable to find app {0} version {1}")]
     AppVersionNotFound(String, String),
+    #[error("App {0} does not define a recommended version")]
+    AppNoRecommended(String),
     #[error("Interaction error: {0}")]
     Interaction(dialoguer::Error),
+    #[error("No terminal available to confirm, pass --yes to proceed")]
+    NoTerminal,
+    #[error("Unrecognized response {0}, expected yes or no")]
+    UnrecognizedResponse(String),
     #[error("Unable to confirm the installation of {0} version {1}: {2}")]
     AppCheck(String, String, std::io::Error),
     #[error("Unable to find installation of {0} version {1}")]
@@ -51,8 +87,22 @@ pub enum CyreneError {
     Download(String, reqwest::Error),
     #[error("Unable to save download from {0}: {1}")]
     DownloadWrite(String, std::io::Error),
+    #[error("Unable to read local source {0}: {1}")]
+    LocalSourceRead(PathBuf, std::io::Error),
+    #[error("Unable to read app list from {0}: {1}")]
+    AppListRead(PathBuf, std::io::Error),
+    #[error("Installing from a local source requires exactly one app")]
+    LocalSourceMultipleApps,
     #[error("Somehow unable to access the current executable")]
     ExeCheck(std::io::Error),
+    #[error("Unable to access the current directory: {0}")]
+    CurrentDir(std::io::Error),
+    #[error("{0} does not provide {1}")]
+    AppBinaryNotFound(String, String),
+    #[error("{0} provides several binaries, pick one of: {1}")]
+    AppBinaryAmbiguous(String, String),
+    #[error("Unable to execute {0}: {1}")]
+    AppExec(PathBuf, std::io::Error),
     #[error("Cyrene was about to close the causality loop")]
     AppLinkingToSelf,
     #[error("Unable to read link in {0}: {1}")]
@@ -67,6 +117,14 @@ pub enum CyreneError {
     AppRemove(String, String, std::io::Error),
     #[error("Non existent app {0} version {1} in lockfile")]
     LockfileAppVersion(String, String),
+    #[error("{0} versions in lockfile are no longer available")]
+    LockfileUnavailable(usize),
+    #[error("Plugin {0} timed out after {1} seconds")]
+    PluginTimeout(String, u64),
+    #[error("Unable to remove partial installation at {0}: {1}")]
+    InstallRollback(PathBuf, std::io::Error),
+    #[error("Installation of {0} version {1} timed out after {2} seconds")]
+    InstallTimeout(String, String, u64),
     #[error("Unable to read config: {0}")]
     ConfigRead(std::io::Error),
     #[error("Unable to write config: {0}")]
@@ -75,4 +133,60 @@ pub enum CyreneError {
     ConfigDeserialize(toml::de::Error),
     #[error("Unable to serialize config: {0}")]
     ConfigSerialize(toml::ser::Error),
+    #[error("{0} issues found")]
+    IssuesFound(usize),
+    #[error("{0} apps failed")]
+    AppsFailed(usize),
+    #[error("{0} plugins failed to parse")]
+    PluginsFailed(usize),
+    #[error("{0} versions are required by loaded lockfiles, pass --force to uninstall them")]
+    RequiredByLockfile(usize),
+    #[error("{0} apps are outdated")]
+    AppsOutdated(usize),
+    #[error("Unable to fetch checksums from {0}: {1}")]
+    ChecksumFetch(String, reqwest::Error),
+    #[error("No checksum for {0} in {1}")]
+    ChecksumNotFound(String, String),
+    #[error("Unable to read {0} for checksum: {1}")]
+    ChecksumRead(PathBuf, std::io::Error),
+    #[error("Checksum mismatch for {0}: expected {1}, got {2}")]
+    ChecksumMismatch(String, String, String),
+    #[error("Unable to fetch signature from {0}: {1}")]
+    SignatureFetch(String, reqwest::Error),
+    #[error("Unable to read {0} for signature verification: {1}")]
+    SignatureRead(PathBuf, std::io::Error),
+    #[error("Signature verification failed for {0}: {1}")]
+    SignatureVerify(PathBuf, String),
+    #[error("Unrecognized archive format in {0}")]
+    UnknownArchiveFormat(String),
+    #[error("{0} is a zip archive, use the zip source type")]
+    ArchiveIsZip(String),
+    #[error("{0} not found in {1}")]
+    ArchiveMemberNotFound(String, String),
+    #[error("Unable to read snapshot from {0}: {1}")]
+    SnapshotRead(PathBuf, std::io::Error),
+    #[error("Unable to write snapshot to {0}: {1}")]
+    SnapshotWrite(PathBuf, std::io::Error),
+    #[error("Unable to deserialize snapshot: {0}")]
+    SnapshotDeserialize(toml::de::Error),
+    #[error("Unable to serialize snapshot: {0}")]
+    SnapshotSerialize(toml::ser::Error),
+    #[error("No registry_url is configured")]
+    RegistryNotConfigured,
+    #[error("Unable to fetch from registry at {0}: {1}")]
+    RegistryFetch(String, reqwest::Error),
+    #[error("Unable to read registry index: {0}")]
+    RegistryRead(std::io::Error),
+    #[error("Unable to write registry index: {0}")]
+    RegistryWrite(std::io::Error),
+    #[error("Unable to deserialize registry index: {0}")]
+    RegistryDeserialize(toml::de::Error),
+    #[error("Plugin {0} is not in the registry")]
+    RegistryPluginNotFound(String),
+    #[error("Plugin {0} already exists at {1}")]
+    PluginExists(String, PathBuf),
+    #[error("Invalid plugin name {0}")]
+    PluginNameInvalid(String),
+    #[error("Unable to write plugin to {0}: {1}")]
+    PluginWrite(PathBuf, std::io::Error),
 }
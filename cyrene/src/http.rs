@@ -0,0 +1,64 @@
+use std::{fs, time::Duration};
+
+use reqwest::{Certificate, Client, ClientBuilder, Proxy};
+
+use crate::{config::CyreneConfig, errors::CyreneError};
+
+/// User-Agent sent with every request
+pub const USER_AGENT: &str = "damillora-cyrene";
+
+/// Client builder with the proxy, CA bundle, timeout and User-Agent from the config applied
+pub fn client_builder(config: &CyreneConfig) -> Result<ClientBuilder, CyreneError> {
+    let mut builder = Client::builder().user_agent(USER_AGENT);
+    if let Some(proxy) = &config.proxy {
+        let proxy = Proxy::all(proxy).map_err(|e| CyreneError::HttpProxy(proxy.clone(), e))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(ca_bundle) = &config.ca_bundle {
+        let pem =
+            fs::read(ca_bundle).map_err(|e| CyreneError::CaBundleRead(ca_bundle.clone(), e))?;
+        let certs = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| CyreneError::CaBundleParse(ca_bundle.clone(), e))?;
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    if let Some(timeout) = config.http_timeout {
+        builder = builder.timeout(Duration::from_secs(timeout));
+    }
+
+    Ok(builder)
+}
+
+/// HTTP client used for every request cyrene makes
+pub fn build_http_client(config: &CyreneConfig) -> Result<Client, CyreneError> {
+    client_builder(config)?
+        .build()
+        .map_err(CyreneError::HttpClient)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_build_http_client() {
+        let config = CyreneConfig {
+            proxy: Some("http://127.0.0.1:3128".to_string()),
+            http_timeout: Some(10),
+            ..Default::default()
+        };
+        assert!(build_http_client(&config).is_ok());
+
+        let config = CyreneConfig {
+            ca_bundle: Some(PathBuf::from("/nonexistent/ca.pem")),
+            ..Default::default()
+        };
+        assert!(matches!(
+            build_http_client(&config),
+            Err(CyreneError::CaBundleRead(_, _))
+        ));
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::OwnedMutexGuard;
+
+/// Locks serializing operations on the same app version
+#[derive(Default)]
+pub struct CyreneInstallLocks {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl CyreneInstallLocks {
+    /// Wait until no other operation holds the lock for this app version
+    pub async fn lock(&self, name: &str, version: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            locks
+                .entry(format!("{}@{}", name, version))
+                .or_default()
+                .clone()
+        };
+        lock.lock_owned().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    async fn install(
+        locks: Arc<CyreneInstallLocks>,
+        running: Arc<AtomicUsize>,
+        version: &str,
+    ) -> usize {
+        let _guard = locks.lock("node", version).await;
+        let concurrent = running.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        running.fetch_sub(1, Ordering::SeqCst);
+
+        concurrent
+    }
+
+    #[tokio::test]
+    async fn test_same_version_serialized() {
+        let locks = Arc::new(CyreneInstallLocks::default());
+        let running = Arc::new(AtomicUsize::new(0));
+
+        let first = tokio::spawn(install(locks.clone(), running.clone(), "22.0.0"));
+        let second = tokio::spawn(install(locks.clone(), running.clone(), "22.0.0"));
+
+        assert_eq!(first.await.unwrap(), 1);
+        assert_eq!(second.await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_different_versions_parallel() {
+        let locks = Arc::new(CyreneInstallLocks::default());
+        let running = Arc::new(AtomicUsize::new(0));
+
+        let first = tokio::spawn(install(locks.clone(), running.clone(), "22.0.0"));
+        let second = tokio::spawn(install(locks.clone(), running.clone(), "20.0.0"));
+
+        let max = first.await.unwrap().max(second.await.unwrap());
+        assert_eq!(max, 2);
+    }
+}
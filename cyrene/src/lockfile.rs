@@ -7,17 +7,147 @@ use std::{
 use log::debug;
 use serde::{Deserialize, Serialize};
 
-use crate::errors::CyreneError;
+use crate::{errors::CyreneError, util};
+
+/// Version of an app in a lockfile, optionally with extra details
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CyreneLockfileEntry {
+    /// `name = "version"`
+    Version(String),
+    /// `name = { version = "version", ... }`
+    Detailed(CyreneLockfileDetails),
+}
+
+/// Path of a fixed lockfile, with the name, old version and new version of each changed entry
+pub type CyreneLockfileFix = (PathBuf, Vec<(String, String, Option<String>)>);
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CyreneLockfileDetails {
+    pub version: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Version requirement or channel the app was installed with, used when upgrading
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<String>,
+}
+
+impl CyreneLockfileEntry {
+    pub fn version(&self) -> &str {
+        match self {
+            CyreneLockfileEntry::Version(version) => version,
+            CyreneLockfileEntry::Detailed(details) => &details.version,
+        }
+    }
+
+    /// Change the version, keeping other details
+    pub fn set_version(&mut self, version: &str) {
+        match self {
+            CyreneLockfileEntry::Version(current) => *current = version.to_string(),
+            CyreneLockfileEntry::Detailed(details) => details.version = version.to_string(),
+        }
+    }
+
+    /// Normalize the version, e.g. `v1.2.3` to `1.2.3`. Returns the previous version if it changed
+    pub fn normalize(&mut self) -> Option<String> {
+        let version = self.version().to_string();
+        let normalized = util::normalize_version(&version);
+        if normalized.eq(&version) {
+            return None;
+        }
+        self.set_version(normalized);
+        Some(version)
+    }
+
+    pub fn range(&self) -> Option<&str> {
+        match self {
+            CyreneLockfileEntry::Version(_) => None,
+            CyreneLockfileEntry::Detailed(details) => details.range.as_deref(),
+        }
+    }
+
+    /// Change the version requirement, keeping other details
+    pub fn set_range(&mut self, range: Option<&str>) {
+        match self {
+            CyreneLockfileEntry::Version(version) => {
+                if let Some(range) = range {
+                    *self = CyreneLockfileEntry::Detailed(CyreneLockfileDetails {
+                        version: version.clone(),
+                        range: Some(range.to_string()),
+                        ..Default::default()
+                    });
+                }
+            }
+            CyreneLockfileEntry::Detailed(details) => details.range = range.map(String::from),
+        }
+    }
+}
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct CyreneLockfile {
-    pub versions: BTreeMap<String, String>,
+    pub versions: BTreeMap<String, CyreneLockfileEntry>,
     pub loaded_lockfile: Option<String>,
+    /// Loaded lockfiles, from lowest to highest priority
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub loaded_lockfiles: Vec<String>,
+}
+impl CyreneLockfile {
+    /// Loaded lockfiles, from lowest to highest priority
+    pub fn lockfile_chain(&self) -> Vec<String> {
+        self.loaded_lockfile
+            .iter()
+            .chain(self.loaded_lockfiles.iter())
+            .cloned()
+            .collect()
+    }
 }
 
 pub struct CyreneLockfileManager {
     lockfile_path: PathBuf,
 }
+// Private functions
+impl CyreneLockfileManager {
+    fn read_global_lockfile(&self) -> Result<CyreneLockfile, CyreneError> {
+        if !fs::exists(&self.lockfile_path).map_err(CyreneError::LockfileRead)? {
+            Ok(CyreneLockfile::default())
+        } else {
+            let lockfile_read =
+                fs::read_to_string(&self.lockfile_path).map_err(CyreneError::LockfileRead)?;
+            let lockfile: CyreneLockfile =
+                toml::de::from_str(&lockfile_read).map_err(CyreneError::LockfileDeserialize)?;
+            Ok(lockfile)
+        }
+    }
+    fn read_local_lockfile(&self, loaded_lockfile: &str) -> Result<CyreneLockfile, CyreneError> {
+        let lockfile_read = fs::read_to_string(loaded_lockfile)
+            .map_err(|e| CyreneError::LockfileLocalRead(PathBuf::from(loaded_lockfile), e))?;
+        let lockfile: CyreneLockfile =
+            toml::de::from_str(&lockfile_read).map_err(CyreneError::LockfileDeserialize)?;
+        Ok(lockfile)
+    }
+    /// Lockfile that changes are saved to, with its path
+    fn read_current_lockfile(&self) -> Result<(PathBuf, CyreneLockfile), CyreneError> {
+        let lockfile = self.read_global_lockfile()?;
+        if let Some(loaded_lockfile) = lockfile.lockfile_chain().last() {
+            // Save changes to the lockfile with the highest priority
+            return Ok((
+                PathBuf::from(loaded_lockfile),
+                self.read_local_lockfile(loaded_lockfile)?,
+            ));
+        }
+        Ok((self.lockfile_path.clone(), lockfile))
+    }
+    fn write_global_lockfile(&self, lockfile: &CyreneLockfile) -> Result<(), CyreneError> {
+        let lockfile_write =
+            toml::ser::to_string(lockfile).map_err(CyreneError::LockfileSerialize)?;
+        fs::write(&self.lockfile_path, lockfile_write).map_err(CyreneError::LockfileWrite)?;
+        Ok(())
+    }
+}
 impl CyreneLockfileManager {
     pub fn new(lockfile_path: &Path) -> Self {
         Self {
@@ -28,63 +158,21 @@ impl CyreneLockfileManager {
         &self,
         name: &str,
     ) -> Result<Option<String>, CyreneError> {
-        let mut lockfile = if !fs::exists(&self.lockfile_path).map_err(CyreneError::LockfileRead)? {
-            CyreneLockfile::default()
-        } else {
-            let lockfile_read =
-                fs::read_to_string(&self.lockfile_path).map_err(CyreneError::LockfileRead)?;
-            let lockfile: CyreneLockfile =
-                toml::de::from_str(&lockfile_read).map_err(CyreneError::LockfileDeserialize)?;
-            lockfile
-        };
-        if let Some(loaded_lockfile) = lockfile.loaded_lockfile {
-            // Merge global lockfile with local ones
-            let new_lockfile = {
-                let lockfile_read = fs::read_to_string(&loaded_lockfile).map_err(|e| {
-                    CyreneError::LockfileLocalRead(PathBuf::from(loaded_lockfile), e)
-                })?;
-                let new_lockfile: CyreneLockfile =
-                    toml::de::from_str(&lockfile_read).map_err(CyreneError::LockfileDeserialize)?;
-
-                new_lockfile
-            };
-            for (key, value) in new_lockfile.versions {
-                lockfile.versions.insert(key, value);
-            }
-        }
-        let version = lockfile.versions.get(name).map(|x| x.to_string());
+        let versions = self.load_version_map_from_current_lockfile()?;
+        let version = versions.get(name).map(|x| x.to_string());
         debug!("lockfile found app {} version {:?}", &name, &version);
         Ok(version)
     }
 
     pub fn update_lockfile(&self, name: &str, version: Option<&str>) -> Result<(), CyreneError> {
-        let mut lockfile_path = PathBuf::from(&self.lockfile_path);
-        let mut lockfile = if !fs::exists(&lockfile_path).map_err(CyreneError::LockfileRead)? {
-            CyreneLockfile::default()
-        } else {
-            let lockfile_read =
-                fs::read_to_string(&lockfile_path).map_err(CyreneError::LockfileRead)?;
-            let lockfile: CyreneLockfile =
-                toml::de::from_str(&lockfile_read).map_err(CyreneError::LockfileDeserialize)?;
-            lockfile
-        };
-        if let Some(loaded_lockfile) = lockfile.loaded_lockfile {
-            // Save changes to new lockfile
-            lockfile = {
-                lockfile_path = PathBuf::from(&loaded_lockfile);
-                let lockfile_read = fs::read_to_string(&loaded_lockfile).map_err(|e| {
-                    CyreneError::LockfileLocalRead(PathBuf::from(&loaded_lockfile), e)
-                })?;
-                let lockfile: CyreneLockfile =
-                    toml::de::from_str(&lockfile_read).map_err(CyreneError::LockfileDeserialize)?;
-                lockfile
-            }
-        }
+        let (lockfile_path, mut lockfile) = self.read_current_lockfile()?;
         debug!("Using lockfile {}", lockfile_path.to_string_lossy());
         if let Some(version) = version {
             lockfile
                 .versions
-                .insert(name.to_owned(), version.to_owned());
+                .entry(name.to_owned())
+                .and_modify(|f| f.set_version(version))
+                .or_insert_with(|| CyreneLockfileEntry::Version(version.to_owned()));
         } else {
             lockfile.versions.remove(name);
         }
@@ -94,68 +182,310 @@ impl CyreneLockfileManager {
         Ok(())
     }
 
-    pub fn use_default_lockfile(&self) -> Result<(), CyreneError> {
-        let mut lockfile = if !fs::exists(&self.lockfile_path).map_err(CyreneError::LockfileRead)? {
-            CyreneLockfile::default()
-        } else {
-            let lockfile_read =
-                fs::read_to_string(&self.lockfile_path).map_err(CyreneError::LockfileRead)?;
-            let lockfile: CyreneLockfile =
-                toml::de::from_str(&lockfile_read).map_err(CyreneError::LockfileDeserialize)?;
-            lockfile
+    pub fn update_lockfile_range(
+        &self,
+        name: &str,
+        range: Option<&str>,
+    ) -> Result<(), CyreneError> {
+        let (lockfile_path, mut lockfile) = self.read_current_lockfile()?;
+        let Some(entry) = lockfile.versions.get_mut(name) else {
+            return Ok(());
         };
-        lockfile.loaded_lockfile = None;
+        entry.set_range(range);
         let lockfile_write =
             toml::ser::to_string(&lockfile).map_err(CyreneError::LockfileSerialize)?;
-        fs::write(&self.lockfile_path, lockfile_write).map_err(CyreneError::LockfileWrite)?;
+        fs::write(lockfile_path, lockfile_write).map_err(CyreneError::LockfileWrite)?;
         Ok(())
     }
 
-    pub fn use_local_lockfile(&self, loaded_lockfile: &Path) -> Result<(), CyreneError> {
-        let mut lockfile = if !fs::exists(&self.lockfile_path).map_err(CyreneError::LockfileRead)? {
-            CyreneLockfile::default()
-        } else {
-            let lockfile_read =
-                fs::read_to_string(&self.lockfile_path).map_err(CyreneError::LockfileRead)?;
-            let lockfile: CyreneLockfile =
-                toml::de::from_str(&lockfile_read).map_err(CyreneError::LockfileDeserialize)?;
-            lockfile
+    pub fn write_lockfile(
+        &self,
+        lockfile_path: &Path,
+        versions: BTreeMap<String, String>,
+    ) -> Result<(), CyreneError> {
+        let lockfile = CyreneLockfile {
+            versions: versions
+                .into_iter()
+                .map(|(name, version)| (name, CyreneLockfileEntry::Version(version)))
+                .collect(),
+            ..Default::default()
         };
-        lockfile.loaded_lockfile = Some(
-            fs::canonicalize(loaded_lockfile)
-                .map_err(|e| CyreneError::LockfileLocalRead(loaded_lockfile.to_path_buf(), e))?
-                .to_string_lossy()
-                .to_string(),
-        );
+        debug!("Writing lockfile {}", lockfile_path.to_string_lossy());
         let lockfile_write =
             toml::ser::to_string(&lockfile).map_err(CyreneError::LockfileSerialize)?;
-        fs::write(&self.lockfile_path, lockfile_write).map_err(CyreneError::LockfileWrite)?;
+        fs::write(lockfile_path, lockfile_write).map_err(CyreneError::LockfileWrite)?;
         Ok(())
     }
 
+    /// Drop entries that `keep` rejects and normalize versions in the current lockfile.
+    ///
+    /// Returns the path of the lockfile and the changed entries, with `None` for dropped ones.
+    pub fn fix_lockfile(
+        &self,
+        keep: impl Fn(&str) -> bool,
+    ) -> Result<CyreneLockfileFix, CyreneError> {
+        let (lockfile_path, mut lockfile) = self.read_current_lockfile()?;
+        let mut changes = Vec::new();
+        lockfile.versions.retain(|name, entry| {
+            if keep(name) {
+                return true;
+            }
+            changes.push((name.clone(), entry.version().to_string(), None));
+            false
+        });
+        for (name, entry) in lockfile.versions.iter_mut() {
+            if let Some(version) = entry.normalize() {
+                changes.push((name.clone(), version, Some(entry.version().to_string())));
+            }
+        }
+        if changes.is_empty() {
+            return Ok((lockfile_path, changes));
+        }
+
+        // Write next to the lockfile, then move it into place
+        let mut temp_path = lockfile_path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let lockfile_write =
+            toml::ser::to_string(&lockfile).map_err(CyreneError::LockfileSerialize)?;
+        fs::write(&temp_path, lockfile_write).map_err(CyreneError::LockfileWrite)?;
+        fs::rename(&temp_path, &lockfile_path).map_err(CyreneError::LockfileWrite)?;
+
+        Ok((lockfile_path, changes))
+    }
+
+    pub fn use_default_lockfile(&self) -> Result<(), CyreneError> {
+        let mut lockfile = self.read_global_lockfile()?;
+        lockfile.loaded_lockfile = None;
+        lockfile.loaded_lockfiles.clear();
+        self.write_global_lockfile(&lockfile)
+    }
+
+    pub fn use_local_lockfiles(&self, loaded_lockfiles: &[PathBuf]) -> Result<(), CyreneError> {
+        let mut lockfile = self.read_global_lockfile()?;
+        let mut canonical_lockfiles = Vec::new();
+        for loaded_lockfile in loaded_lockfiles {
+            canonical_lockfiles.push(
+                fs::canonicalize(loaded_lockfile)
+                    .map_err(|e| CyreneError::LockfileLocalRead(loaded_lockfile.to_path_buf(), e))?
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+        lockfile.loaded_lockfile = None;
+        lockfile.loaded_lockfiles = canonical_lockfiles;
+        self.write_global_lockfile(&lockfile)
+    }
+
+    pub fn get_loaded_lockfiles(&self) -> Result<Vec<String>, CyreneError> {
+        Ok(self.read_global_lockfile()?.lockfile_chain())
+    }
+
+    /// Version of an app in the nearest `cyrene.lock` in `dir` or its parents
+    pub fn find_version_in_nearest_lockfile(
+        &self,
+        dir: &Path,
+        name: &str,
+    ) -> Result<Option<String>, CyreneError> {
+        for ancestor in dir.ancestors() {
+            let lockfile_path = ancestor.join("cyrene.lock");
+            if lockfile_path.is_file() {
+                debug!("Using lockfile {}", lockfile_path.to_string_lossy());
+                let lockfile = self.read_local_lockfile(&lockfile_path.to_string_lossy())?;
+                return Ok(lockfile
+                    .versions
+                    .get(name)
+                    .map(|f| util::normalize_version(f.version()).to_string()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Versions in loaded local lockfiles, with the lockfile each one comes from
+    pub fn load_local_versions(&self) -> Result<BTreeMap<String, (String, String)>, CyreneError> {
+        let mut versions = BTreeMap::new();
+        // Later lockfiles override earlier ones
+        for loaded_lockfile in self.get_loaded_lockfiles()? {
+            let local_lockfile = self.read_local_lockfile(&loaded_lockfile)?;
+            for (name, entry) in local_lockfile.versions {
+                let version = util::normalize_version(entry.version()).to_string();
+                versions.insert(name, (version, loaded_lockfile.clone()));
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Entries of the current lockfiles, with versions normalized so they match installation paths
+    pub fn load_entries_from_current_lockfile(
+        &self,
+    ) -> Result<BTreeMap<String, CyreneLockfileEntry>, CyreneError> {
+        let lockfile = self.read_global_lockfile()?;
+        let mut versions = lockfile.versions.clone();
+        // Later lockfiles override earlier ones
+        for loaded_lockfile in lockfile.lockfile_chain() {
+            let local_lockfile = self.read_local_lockfile(&loaded_lockfile)?;
+            versions.extend(local_lockfile.versions);
+        }
+        for entry in versions.values_mut() {
+            entry.normalize();
+        }
+        Ok(versions)
+    }
+
     pub fn load_version_map_from_current_lockfile(
         &self,
     ) -> Result<BTreeMap<String, String>, CyreneError> {
-        let mut lockfile = if !fs::exists(&self.lockfile_path).map_err(CyreneError::LockfileRead)? {
-            CyreneLockfile::default()
-        } else {
-            let lockfile_read =
-                fs::read_to_string(&self.lockfile_path).map_err(CyreneError::LockfileRead)?;
-            let lockfile: CyreneLockfile =
-                toml::de::from_str(&lockfile_read).map_err(CyreneError::LockfileDeserialize)?;
-            lockfile
-        };
-        if let Some(loaded_lockfile) = lockfile.loaded_lockfile {
-            // Load needed versions from new lockfile
-            lockfile = {
-                let lockfile_read = fs::read_to_string(&loaded_lockfile).map_err(|e| {
-                    CyreneError::LockfileLocalRead(PathBuf::from(loaded_lockfile), e)
-                })?;
-                let lockfile: CyreneLockfile =
-                    toml::de::from_str(&lockfile_read).map_err(CyreneError::LockfileDeserialize)?;
-                lockfile
-            };
-        }
-        Ok(lockfile.versions.clone())
+        Ok(self
+            .load_entries_from_current_lockfile()?
+            .into_iter()
+            .map(|(name, entry)| (name, entry.version().to_string()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_entries_round_trip() {
+        let lockfile_read = "[versions]\nnode = \"22.0.0\"\n";
+        let lockfile: CyreneLockfile = toml::de::from_str(lockfile_read).unwrap();
+
+        assert_eq!(
+            lockfile.versions.get("node"),
+            Some(&CyreneLockfileEntry::Version("22.0.0".to_string()))
+        );
+        assert_eq!(toml::ser::to_string(&lockfile).unwrap(), lockfile_read);
+    }
+
+    #[test]
+    fn test_detailed_entries_round_trip() {
+        let lockfile_read = r#"
+[versions]
+go = "1.24.0"
+node = { version = "22.0.0", pinned = true, source = "registry" }
+"#;
+        let mut lockfile: CyreneLockfile = toml::de::from_str(lockfile_read).unwrap();
+        let node = lockfile.versions.get_mut("node").unwrap();
+        assert_eq!(node.version(), "22.0.0");
+        node.set_version("22.1.0");
+
+        let lockfile_write = toml::ser::to_string(&lockfile).unwrap();
+        let lockfile: CyreneLockfile = toml::de::from_str(&lockfile_write).unwrap();
+        assert_eq!(
+            lockfile.versions.get("node"),
+            Some(&CyreneLockfileEntry::Detailed(CyreneLockfileDetails {
+                version: "22.1.0".to_string(),
+                pinned: true,
+                integrity: None,
+                source: Some("registry".to_string()),
+                range: None,
+            }))
+        );
+        assert_eq!(lockfile.versions.get("go").unwrap().version(), "1.24.0");
+    }
+
+    #[test]
+    fn test_range_round_trip() {
+        let mut lockfile: CyreneLockfile =
+            toml::de::from_str("[versions]\nnode = \"20.1.0\"\n").unwrap();
+        let node = lockfile.versions.get_mut("node").unwrap();
+        node.set_range(Some("^20"));
+        node.set_version("20.2.0");
+
+        let lockfile_write = toml::ser::to_string(&lockfile).unwrap();
+        let lockfile: CyreneLockfile = toml::de::from_str(&lockfile_write).unwrap();
+        let node = lockfile.versions.get("node").unwrap();
+        assert_eq!(node.version(), "20.2.0");
+        assert_eq!(node.range(), Some("^20"));
+    }
+
+    #[test]
+    fn test_v_prefixed_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let lockfile_path = dir.path().join("cyrene.lock");
+        fs::write(
+            &lockfile_path,
+            "[versions]\nnode = \"v20.1.0\"\ngo = { version = \"v1.24.0\", pinned = true }\n",
+        )
+        .unwrap();
+
+        let manager = CyreneLockfileManager::new(&lockfile_path);
+        let versions = manager.load_version_map_from_current_lockfile().unwrap();
+        assert_eq!(versions.get("node").unwrap(), "20.1.0");
+        assert_eq!(versions.get("go").unwrap(), "1.24.0");
+        assert_eq!(
+            manager
+                .find_version_in_nearest_lockfile(dir.path(), "node")
+                .unwrap(),
+            Some("20.1.0".to_string())
+        );
+
+        let (_, changes) = manager.fix_lockfile(|_| true).unwrap();
+        assert_eq!(changes.len(), 2);
+        let lockfile: CyreneLockfile =
+            toml::de::from_str(&fs::read_to_string(&lockfile_path).unwrap()).unwrap();
+        assert_eq!(lockfile.versions.get("node").unwrap().version(), "20.1.0");
+        let go = lockfile.versions.get("go").unwrap();
+        assert_eq!(go.version(), "1.24.0");
+        assert!(matches!(go, CyreneLockfileEntry::Detailed(d) if d.pinned));
+    }
+
+    #[test]
+    fn test_load_local_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project.lock");
+        fs::write(&project, "[versions]\nnode = \"v20.1.0\"\n").unwrap();
+        fs::write(
+            dir.path().join("global.lock"),
+            "[versions]\ngo = \"1.24.0\"\n",
+        )
+        .unwrap();
+
+        let manager = CyreneLockfileManager::new(&dir.path().join("global.lock"));
+        assert!(manager.load_local_versions().unwrap().is_empty());
+
+        manager
+            .use_local_lockfiles(std::slice::from_ref(&project))
+            .unwrap();
+        let versions = manager.load_local_versions().unwrap();
+        assert_eq!(versions.len(), 1);
+        let (version, lockfile) = versions.get("node").unwrap();
+        assert_eq!(version, "20.1.0");
+        assert_eq!(PathBuf::from(lockfile), fs::canonicalize(&project).unwrap());
+    }
+
+    #[test]
+    fn test_find_version_in_nearest_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        let nested = project.join("src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            project.join("cyrene.lock"),
+            "[versions]\nnode = \"20.0.0\"\n",
+        )
+        .unwrap();
+
+        let manager = CyreneLockfileManager::new(&dir.path().join("global.lock"));
+        assert_eq!(
+            manager
+                .find_version_in_nearest_lockfile(&nested, "node")
+                .unwrap(),
+            Some("20.0.0".to_string())
+        );
+        assert_eq!(
+            manager
+                .find_version_in_nearest_lockfile(&nested, "go")
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            manager
+                .find_version_in_nearest_lockfile(dir.path(), "node")
+                .unwrap(),
+            None
+        );
     }
 }
@@ -1,20 +1,33 @@
-use std::{fs, path::PathBuf, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{self, IsTerminal},
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+};
 
 use crate::{
     tables::CyreneAppVersionsAllRow, util::is_major_version_equal, version::CyreneVersion,
 };
 use clap::{Args, Parser, Subcommand};
-use console::{Color, Style, style};
-use dialoguer::Confirm;
+use console::{Color, Style, StyledObject, style};
+use dialoguer::Input;
 use dialoguer::theme::ColorfulTheme;
 use log::debug;
 use miette::{ErrReport, IntoDiagnostic};
 
 use crate::{
+    config::CyreneConfig,
     dirs::CyreneDirs,
     errors::CyreneError,
-    lockfile::CyreneLockfileManager,
+    lockfile::{CyreneLockfile, CyreneLockfileManager},
     manager::CyreneManager,
+    output::{CyreneEvent, OutputFormat, outputln, resultln},
+    registry::CyreneRegistryManager,
+    shell::Shell,
+    snapshot::CyreneSnapshot,
     transaction::{TransactionCommands, TransactionExecutor},
     versions_cache::CyreneVersionCacheManager,
 };
@@ -27,12 +40,26 @@ mod app_module;
 mod config;
 /// Directories
 mod dirs;
+/// Health checks
+mod doctor;
 /// Errors
 mod errors;
+/// HTTP client
+mod http;
+/// Installation locks
+mod install_lock;
 /// Lockfile
 mod lockfile;
 /// Manager
 mod manager;
+/// Output formatting
+mod output;
+/// Plugin registry
+mod registry;
+/// Shell snippets
+mod shell;
+/// State snapshots
+mod snapshot;
 /// Table models
 mod tables;
 /// Install transactions
@@ -79,8 +106,26 @@ impl From<&String> for AppVersion {
 #[command(version, about, long_about = None)]
 pub struct Cli {
     /// Configuration file location
-    #[arg(short = 'c', long)]
+    #[arg(short = 'c', long, global = true)]
     config: Option<String>,
+    /// Output format
+    #[arg(long, value_enum, global = true, default_value_t)]
+    output: OutputFormat,
+    /// Load plugins from this directory instead of the plugins directory
+    #[arg(long, global = true)]
+    plugin_dir: Option<String>,
+    /// Ignore the version cache, fetching versions every time they are needed
+    #[arg(long, global = true)]
+    no_cache: bool,
+    /// Proceed without asking for confirmation
+    #[arg(short = 'y', long, global = true)]
+    yes: bool,
+    /// Hide progress and status messages, keeping the output of the command
+    #[arg(short = 'q', long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print debug logs, as with RUST_LOG=debug
+    #[arg(short = 'v', long, global = true)]
+    verbose: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -99,29 +144,155 @@ pub enum Commands {
     Link(AppLinkOpts),
     /// Unlink installed binaries
     Unlink(AppUnlinkOpts),
+    /// Switch the linked version of an installed binary
+    Use(AppUseOpts),
     /// List versions of a binary
     Versions(AppVersionsOpts),
     /// Refresh versions of a binary
     Refresh(AppRefreshOpts),
     /// Load cyrene lockfiles in a directory
     Load(AppLoadOpts),
+    /// Check that versions in the current lockfile are available
+    Check,
+    /// List linked apps with a newer version available
+    Outdated(AppOutdatedOpts),
+    /// Inspect plugins
+    Plugin(AppPluginOpts),
+    /// List linked apps providing a binary
+    Provides(AppProvidesOpts),
+    /// Pin versions in the current lockfile to exact versions
+    Freeze(AppFreezeOpts),
+    /// Relink apps whose links drifted from the lockfile
+    Sync,
+    /// Remove and reinstall every app in the lockfile, then relink them
+    ReinstallAll,
+    /// Inspect the current lockfile
+    Lockfile(AppLockfileOpts),
+    /// Inspect the version cache
+    Cache(AppCacheOpts),
+    /// Print a shell snippet that puts linked binaries on PATH
+    Shellenv(AppShellenvOpts),
+    /// Move installed apps into the configured layout
+    Migrate,
+    /// Show information about an app
+    Info(AppInfoOpts),
+    /// Show what installing a version of an app would do, without doing it
+    Explain(AppExplainOpts),
+    /// Search plugins in the registry
+    Search(AppSearchOpts),
+    /// List binaries linked by cyrene and check their targets
+    Linked,
+    /// List binaries of every app in the lockfile and where they resolve
+    Links(AppLinksOpts),
+    /// List binaries that more than one installed app provides
+    Conflicts,
+    /// Run a binary of the version selected by the nearest cyrene.lock
+    Exec(AppExecOpts),
+    /// Print the path of a binary of the version selected by the nearest cyrene.lock
+    Which(AppWhichOpts),
+    /// Run an action defined by an app, in the version selected by the nearest cyrene.lock
+    Run(AppRunOpts),
+    /// Show disk usage of installed apps
+    Du(AppDuOpts),
+    /// Check for dangling links, orphaned apps, missing installs and a corrupt cache
+    Doctor(AppDoctorOpts),
+    /// Print cached versions of an app for shell completion
+    #[command(name = "__complete-versions", hide = true)]
+    CompleteVersions(AppCompleteVersionsOpts),
+    /// Save config, linked versions and installed versions to a file
+    Snapshot(AppSnapshotOpts),
+    /// Restore config and reinstall apps from a snapshot.
+    ///
+    /// Apps are installed using the current config, the restored config applies from the next run.
+    Restore(AppSnapshotOpts),
+}
+
+#[derive(Args)]
+pub struct AppLockfileOpts {
+    #[command(subcommand)]
+    command: LockfileCommands,
+}
+
+#[derive(Subcommand)]
+pub enum LockfileCommands {
+    /// Show the effective lockfile after merging loaded lockfiles
+    Show(LockfileShowOpts),
+    /// Drop entries without a plugin and normalize versions in the current lockfile
+    Fix,
+}
+
+#[derive(Args)]
+pub struct AppPluginOpts {
+    #[command(subcommand)]
+    command: PluginCommands,
+}
+
+#[derive(Subcommand)]
+pub enum PluginCommands {
+    /// Show the recipe of a plugin
+    Show(PluginShowOpts),
+    /// Fetch versions from a plugin without caching them
+    Versions(PluginVersionsOpts),
+    /// Download a plugin from the registry
+    Add(PluginAddOpts),
+    /// Link an installed version into a temporary directory to check its binaries
+    TestLink(PluginTestLinkOpts),
+    /// Parse every plugin and report the ones that fail
+    Verify,
 }
 
 #[derive(Args)]
 pub struct AppInstallOpts {
     /// Name of app
     apps: Vec<String>,
+    /// Refresh versions before installing
+    #[arg(long)]
+    refresh: bool,
+    /// Install from a local file instead of downloading sources
+    #[arg(long)]
+    from: Option<String>,
+    /// Also install apps listed in a file, one name or name@version per line
+    #[arg(long, conflicts_with = "from")]
+    from_file: Option<String>,
+    /// Continue with other apps when one fails to load or resolve
+    #[arg(long)]
+    keep_going: bool,
 }
 #[derive(Args)]
 pub struct AppUpgradeOpts {
     /// Name of app
     apps: Option<Vec<String>>,
+    /// Refresh versions before upgrading
+    #[arg(long)]
+    refresh: bool,
+    /// Continue with other apps when one fails
+    #[arg(long)]
+    keep_going: bool,
+}
+
+#[derive(Args)]
+pub struct AppOutdatedOpts {
+    /// Refresh versions before checking
+    #[arg(long)]
+    refresh: bool,
+    /// Print outdated apps as JSON
+    #[arg(long)]
+    json: bool,
+    /// Exit with an error when any app is outdated
+    #[arg(long)]
+    exit_code: bool,
 }
 
 #[derive(Args)]
 pub struct AppUninstallOpts {
     /// Name of app
     apps: Vec<String>,
+    /// Keep the linked version of apps given without a version
+    #[arg(long)]
+    keep_linked: bool,
+    /// Uninstall versions required by a loaded local lockfile
+    #[arg(long)]
+    force: bool,
 }
 #[derive(Args)]
 pub struct AppLinkOpts {
@@ -131,6 +302,17 @@ pub struct AppLinkOpts {
     version: String,
 }
 #[derive(Args)]
+pub struct AppUseOpts {
+    /// Name of app
+    name: String,
+    /// Version of app
+    #[arg(required_unless_present = "latest_installed")]
+    version: Option<String>,
+    /// Use the latest installed version
+    #[arg(long, conflicts_with = "version")]
+    latest_installed: bool,
+}
+#[derive(Args)]
 pub struct AppUnlinkOpts {
     /// Name of app
     name: String,
@@ -141,79 +323,301 @@ pub struct AppListOpts {
     /// Long format
     #[arg(short = 'l', long)]
     long: bool,
+    /// One unstyled line per installed version, with `*` marking the linked one
+    #[arg(long, conflicts_with = "long")]
+    flat: bool,
 }
 #[derive(Args)]
 pub struct AppVersionsOpts {
-    /// Name of app
-    name: String,
+    /// Name of apps
+    #[arg(required = true)]
+    apps: Vec<String>,
     /// Long format
     #[arg(short = 'l', long)]
     long: bool,
+    /// Only show the latest version of each app
+    #[arg(long)]
+    latest: bool,
 }
 #[derive(Args)]
 pub struct AppRefreshOpts {
     /// Name of app
     name: Option<String>,
+    /// Continue with other apps when one fails
+    #[arg(long)]
+    keep_going: bool,
 }
 #[derive(Args)]
 pub struct AppLoadOpts {
-    /// Custom path to lockfile
-    lockfile: Option<String>,
+    /// Custom paths to lockfiles, later lockfiles override earlier ones
+    lockfiles: Vec<String>,
     /// Use default lockfile
     #[arg(short = 'd', long)]
     default: bool,
+    /// Print how long installing and linking each app took
+    #[arg(long)]
+    timings: bool,
+}
+#[derive(Args)]
+pub struct PluginShowOpts {
+    /// Name of app
+    name: String,
+    /// Only show the path to the recipe
+    #[arg(long)]
+    path: bool,
+}
+#[derive(Args)]
+pub struct PluginVersionsOpts {
+    /// Name of app
+    name: String,
+    /// Long format
+    #[arg(short = 'l', long)]
+    long: bool,
+}
+#[derive(Args)]
+pub struct PluginTestLinkOpts {
+    /// Name of app
+    name: String,
+    /// Installed version to link
+    version: String,
+}
+#[derive(Args)]
+pub struct AppProvidesOpts {
+    /// Name of binary
+    binary: String,
+}
+#[derive(Args)]
+pub struct AppExecOpts {
+    /// Name of app
+    name: String,
+    /// Name of binary
+    binary: String,
+    /// Arguments passed to the binary
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+}
+#[derive(Args)]
+pub struct AppWhichOpts {
+    /// Name of app
+    name: String,
+    /// Name of binary, needed when the app provides several
+    binary: Option<String>,
+}
+#[derive(Args)]
+pub struct AppRunOpts {
+    /// Name of app
+    name: String,
+    /// Name of action
+    action: String,
+    /// Arguments passed to the action
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+}
+#[derive(Args)]
+pub struct AppDoctorOpts {
+    /// Print issues as JSON
+    #[arg(long)]
+    json: bool,
+}
+#[derive(Args)]
+pub struct AppCompleteVersionsOpts {
+    /// Name of app
+    name: String,
+}
+#[derive(Args)]
+pub struct AppDuOpts {
+    /// Show usage of each installed version
+    #[arg(long)]
+    by_version: bool,
+}
+#[derive(Args)]
+pub struct AppFreezeOpts {
+    /// Write the pinned lockfile to this path instead
+    #[arg(short = 'f', long)]
+    file: Option<String>,
+}
+#[derive(Args)]
+pub struct AppSnapshotOpts {
+    /// Path to snapshot file
+    file: String,
+}
+#[derive(Args)]
+pub struct AppSearchOpts {
+    /// Text to search in plugin names and descriptions
+    query: String,
+    /// Refresh the registry index before searching
+    #[arg(long)]
+    refresh: bool,
+}
+#[derive(Args)]
+pub struct PluginAddOpts {
+    /// Name of plugin
+    name: String,
+    /// Refresh the registry index before downloading
+    #[arg(long)]
+    refresh: bool,
+    /// Replace an existing plugin
+    #[arg(short = 'f', long)]
+    force: bool,
+}
+#[derive(Args)]
+pub struct AppInfoOpts {
+    /// Name of app
+    name: String,
+}
+#[derive(Args)]
+pub struct AppLinksOpts {
+    /// Print links as JSON
+    #[arg(long)]
+    json: bool,
+}
+#[derive(Args)]
+pub struct AppExplainOpts {
+    /// Name of app
+    name: String,
+    /// Version of app
+    version: String,
+}
+#[derive(Args)]
+pub struct AppShellenvOpts {
+    /// Shell syntax to print
+    #[arg(long, value_enum, default_value_t)]
+    shell: Shell,
+}
+#[derive(Args)]
+pub struct LockfileShowOpts {
+    /// Print as JSON
+    #[arg(long)]
+    json: bool,
+}
+#[derive(Args)]
+pub struct AppCacheOpts {
+    #[command(subcommand)]
+    command: CacheCommands,
+}
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// Show cached versions of an app without refreshing them
+    Show(CacheShowOpts),
+    /// Remove cached versions of apps that no longer have a plugin
+    Prune,
+}
+#[derive(Args)]
+pub struct CacheShowOpts {
+    /// Name of app
+    name: String,
+    /// Print as JSON
+    #[arg(long)]
+    json: bool,
 }
 #[tokio::main]
 async fn main() -> Result<(), ErrReport> {
-    start().await.into_diagnostic()?;
+    let cli = Cli::parse();
+    let mut logger = env_logger::Builder::from_default_env();
+    if cli.verbose {
+        logger.filter_level(log::LevelFilter::Debug);
+    }
+    logger.init();
+    output::init(cli.output, cli.quiet);
+    if let Err(e) = start(cli).await {
+        output::emit(CyreneEvent::Error {
+            message: e.to_string(),
+        });
+        return Err(e).into_diagnostic();
+    }
 
     Ok(())
 }
-async fn start() -> Result<(), CyreneError> {
-    env_logger::init();
-    let cli = Cli::parse();
-    let dirs = Arc::new(CyreneDirs::new(&cli.config)?);
+async fn start(cli: Cli) -> Result<(), CyreneError> {
+    let config_path = match &cli.config {
+        Some(conf) => PathBuf::from(conf),
+        None => CyreneConfig::default_path(),
+    };
+    let config = Arc::new(CyreneConfig::load(&config_path)?);
+    let mut dirs = CyreneDirs::new(&config)?;
+    if let Some(plugin_dir) = &cli.plugin_dir {
+        dirs.override_plugins_dir(PathBuf::from(plugin_dir));
+    }
+    let dirs = Arc::new(dirs);
     dirs.init_dirs()?;
     let cache_manager = Box::new(CyreneVersionCacheManager::new(&dirs.version_cache_path));
     let lockfile_manager = Box::new(CyreneLockfileManager::new(&dirs.lockfile_path()));
-
-    let actions = Arc::new(CyreneManager::new(
-        dirs.clone(),
-        lockfile_manager,
-        cache_manager,
+    let registry_manager = Box::new(CyreneRegistryManager::new(
+        config.registry_url.as_deref(),
+        &dirs.registry_index_path,
+        http::build_http_client(&config)?,
     ));
 
+    let actions = Arc::new(
+        CyreneManager::new(
+            config.clone(),
+            dirs.clone(),
+            lockfile_manager,
+            cache_manager,
+            registry_manager,
+        )
+        .with_no_cache(cli.no_cache),
+    );
+    let yes = cli.yes;
+    let default_confirm = config.default_confirm();
+
     match cli.command {
         Commands::Install(app_install_opts) => {
-            let app_to_be_installed: Vec<_> =
-                app_install_opts.apps.iter().map(AppVersion::from).collect();
+            if app_install_opts.from.is_some() && app_install_opts.apps.len() != 1 {
+                return Err(CyreneError::LocalSourceMultipleApps);
+            }
+            let mut apps = app_install_opts.apps.clone();
+            if let Some(from_file) = &app_install_opts.from_file {
+                apps.extend(read_app_list(&PathBuf::from(from_file))?);
+            }
+            let app_to_be_installed = parse_app_versions(&actions, &apps);
             let mut app_actions: Vec<AppVersionAction> = Vec::new();
             let mut app_actions_unneeded: Vec<AppVersionAction> = Vec::new();
+            // Installed, but not linked
+            let mut app_actions_link: Vec<AppVersionAction> = Vec::new();
+            // Version requirements given on the command line, kept for upgrades
+            let mut app_ranges: BTreeMap<String, String> = BTreeMap::new();
+            let mut failures: Vec<(String, CyreneError)> = Vec::new();
             for app in app_to_be_installed {
-                let app_config = actions.load_app(&app.name)?;
+                let resolved = async {
+                    if app_install_opts.refresh {
+                        actions.update_versions(&app.name).await?;
+                    }
 
-                let install_version = if let Some(ver) = &app.version {
-                    if !app_config.settings.semver {
-                        ver.to_string()
-                    } else if let CyreneVersion::Semver(_) = CyreneVersion::parse(ver) {
-                        ver.to_string()
-                    } else {
-                        actions
-                            .get_latest_major_release(&app.name, ver.as_str())
-                            .await?
-                            .ok_or(CyreneError::AppVersionNotFound(
-                                app.name.clone(),
-                                ver.to_string(),
-                            ))?
+                    match actions
+                        .resolve_install_version(&app.name, app.version.as_deref())
+                        .await
+                    {
+                        Err(CyreneError::AppVersionNotFound(name, ver)) => {
+                            Err(app_version_not_found(&actions, &name, &ver).await)
+                        }
+                        result => result,
                     }
-                } else {
-                    actions.get_latest_version(&app.name).await?
+                }
+                .await;
+                let install_version = match resolved {
+                    Ok((install_version, range)) => {
+                        if let Some(range) = range {
+                            app_ranges.insert(app.name.clone(), range);
+                        }
+                        install_version
+                    }
+                    Err(e) if app_install_opts.keep_going => {
+                        failures.push((app.name, e));
+                        continue;
+                    }
+                    Err(e) => return Err(e),
                 };
                 if actions.is_version_installed(&app.name, &install_version)? {
-                    app_actions_unneeded.push(AppVersionAction {
+                    let app_action = AppVersionAction {
                         name: app.name,
                         version: install_version,
-                    });
+                    };
+                    if actions.is_version_linked(&app_action.name, &app_action.version)? {
+                        app_actions_unneeded.push(app_action);
+                    } else {
+                        app_actions_link.push(app_action);
+                    }
                 } else {
                     app_actions.push(AppVersionAction {
                         name: app.name,
@@ -222,74 +626,104 @@ async fn start() -> Result<(), CyreneError> {
                 }
             }
             if !app_actions_unneeded.is_empty() {
-                println!();
+                outputln!();
                 tables::cyrene_app_install_unneeded(&app_actions_unneeded);
             }
-            if !app_actions.is_empty() {
-                println!();
-                tables::cyrene_app_install(&app_actions);
-                println!();
+            if !app_actions.is_empty() || !app_actions_link.is_empty() {
+                if !app_actions.is_empty() {
+                    outputln!();
+                    tables::cyrene_app_install(&app_actions);
+                }
+                if !app_actions_link.is_empty() {
+                    outputln!();
+                    tables::cyrene_app_link(&app_actions_link);
+                }
+                outputln!();
 
-                let theme = ColorfulTheme {
-                    prompt_style: Style::new().fg(Color::Color256(219)),
-                    ..Default::default()
+                let action = if app_actions.is_empty() {
+                    "linking"
+                } else {
+                    "installation"
                 };
-                if Confirm::with_theme(&theme)
-                    .default(false)
-                    .show_default(true)
-                    .wait_for_newline(true)
-                    .with_prompt(format!(
-                        "Proceed with {}?",
-                        style("installation").fg(Color::Green).bold()
-                    ))
-                    .interact()
-                    .map_err(CyreneError::Interaction)?
-                {
+                if confirm(style(action).fg(Color::Green).bold(), yes, default_confirm)? {
                     let mut transaction = TransactionExecutor::new(actions.clone());
-                    for app_action in app_actions.iter() {
+                    let app_actions_all = app_actions
+                        .iter()
+                        .map(|f| (f, true))
+                        .chain(app_actions_link.iter().map(|f| (f, false)));
+                    for (app_action, install) in app_actions_all {
                         let linked_version = actions.find_installed_version(&app_action.name)?;
-                        transaction.add(TransactionCommands::Install {
-                            app: app_action.name.clone(),
-                            version: app_action.version.clone(),
-                        });
-                        if let Some(linked_version) = &linked_version
-                            && is_major_version_equal(linked_version, &app_action.version)?
-                        {
+                        if install {
+                            match &app_install_opts.from {
+                                Some(path) => transaction.add(TransactionCommands::InstallLocal {
+                                    app: app_action.name.clone(),
+                                    version: app_action.version.clone(),
+                                    path: PathBuf::from(path),
+                                }),
+                                None => transaction.add(TransactionCommands::Install {
+                                    app: app_action.name.clone(),
+                                    version: app_action.version.clone(),
+                                }),
+                            }
+                        }
+                        let update_lockfile = match &linked_version {
+                            Some(linked_version) => {
+                                is_major_version_equal(linked_version, &app_action.version)?
+                            }
+                            None => true,
+                        };
+                        if update_lockfile {
                             transaction.add(TransactionCommands::LockfileUpdate {
                                 app: app_action.name.clone(),
                                 version: Some(app_action.version.clone()),
                             });
-                        } else if linked_version.is_none() {
-                            transaction.add(TransactionCommands::LockfileUpdate {
+                            transaction.add(TransactionCommands::LockfileRange {
                                 app: app_action.name.clone(),
-                                version: Some(app_action.version.clone()),
+                                range: app_ranges.get(&app_action.name).cloned(),
                             });
                         }
                         transaction.add(TransactionCommands::Link {
                             app: app_action.name.clone(),
                             version: app_action.version.clone(),
-                            overwrite: false,
+                            // Replace links of the previous version when only relinking
+                            overwrite: !install && update_lockfile,
                         });
                     }
 
                     transaction.execute().await?;
                 } else {
-                    println!("{}", style("Aborted").fg(console::Color::Red))
+                    outputln!("{}", style("Aborted").fg(console::Color::Red))
                 }
             } else {
-                println!("{}", style("No action needed").fg(console::Color::Green));
+                outputln!("{}", style("No action needed").fg(console::Color::Green));
             }
 
-            Ok(())
+            report_failures(&failures)
+        }
+        Commands::Upgrade(app_install_opts) => {
+            app_upgrade(actions, &app_install_opts, yes, default_confirm).await
         }
-        Commands::Upgrade(app_install_opts) => app_upgrade(actions, &app_install_opts).await,
         Commands::Uninstall(app_install_opts) => {
-            let app_to_be_installed: Vec<_> =
-                app_install_opts.apps.iter().map(AppVersion::from).collect();
+            let app_to_be_installed = parse_app_versions(&actions, &app_install_opts.apps);
             let mut app_actions: Vec<AppVersion> = Vec::new();
             for app in app_to_be_installed {
                 let app_config = actions.load_app(&app.name)?;
 
+                if app_install_opts.keep_linked
+                    && app.version.is_none()
+                    && let Some(linked_version) = actions.find_installed_version(&app.name)?
+                {
+                    for (name, version) in actions.list_installed_app_versions(&app.name)? {
+                        if !version.eq(&linked_version) {
+                            app_actions.push(AppVersion {
+                                name,
+                                version: Some(version),
+                            });
+                        }
+                    }
+                    continue;
+                }
+
                 let version = match &app.version {
                     Some(version) => {
                         if !app_config.settings.semver {
@@ -316,25 +750,35 @@ async fn start() -> Result<(), CyreneError> {
                     version,
                 });
             }
+            let local_versions = actions.get_local_lockfile_versions()?;
+            let mut required = 0;
+            for app_action in app_actions.iter() {
+                let Some((version, lockfile)) = local_versions.get(&app_action.name) else {
+                    continue;
+                };
+                if app_action.version.as_ref().is_none_or(|f| f.eq(version)) {
+                    eprintln!(
+                        "{} {} {} is required by {}",
+                        style("Warning:").fg(Color::Yellow).bold(),
+                        style(&app_action.name).fg(Color::Color256(219)).bold(),
+                        style(version).fg(Color::Green),
+                        lockfile
+                    );
+                    required += 1;
+                }
+            }
+            if required > 0 && !app_install_opts.force {
+                return Err(CyreneError::RequiredByLockfile(required));
+            }
             if !app_actions.is_empty() {
-                println!();
+                outputln!();
                 tables::cyrene_app_remove(&app_actions);
-                println!();
-                let theme = ColorfulTheme {
-                    prompt_style: Style::new().fg(Color::Color256(219)),
-                    ..Default::default()
-                };
-                if Confirm::with_theme(&theme)
-                    .default(false)
-                    .show_default(true)
-                    .wait_for_newline(true)
-                    .with_prompt(format!(
-                        "Proceed with {}?",
-                        style("uninstallation").fg(Color::Red).bold()
-                    ))
-                    .interact()
-                    .map_err(CyreneError::Interaction)?
-                {
+                outputln!();
+                if confirm(
+                    style("uninstallation").fg(Color::Red).bold(),
+                    yes,
+                    default_confirm,
+                )? {
                     let mut transaction = TransactionExecutor::new(actions.clone());
                     for app_action in app_actions.iter() {
                         match &app_action.version {
@@ -402,55 +846,39 @@ async fn start() -> Result<(), CyreneError> {
 
                     transaction.execute().await?;
                 } else {
-                    println!("{}", style("Aborted").fg(console::Color::Red));
+                    outputln!("{}", style("Aborted").fg(console::Color::Red));
                 }
             } else {
-                println!("{}", style("No action needed").fg(console::Color::Green));
+                outputln!("{}", style("No action needed").fg(console::Color::Green));
             }
             Ok(())
         }
         Commands::Link(app_install_opts) => {
-            let app_config = actions.load_app(&app_install_opts.name)?;
-
-            let version = if !app_config.settings.semver {
-                Some(app_install_opts.version.clone())
-            } else if let CyreneVersion::Semver(_) = CyreneVersion::parse(&app_install_opts.version)
-            {
-                Some(app_install_opts.version.clone())
-            } else {
-                actions.find_installed_major_release(
-                    &app_install_opts.name,
-                    &app_install_opts.version,
+            let name = actions.canonical_app_name(&app_install_opts.name);
+            app_link(actions, &name, &app_install_opts.version).await
+        }
+        Commands::Use(app_use_opts) => {
+            let name = actions.canonical_app_name(&app_use_opts.name);
+            let version = if app_use_opts.latest_installed {
+                actions.find_installed_major_release(&name, "*")?.ok_or(
+                    CyreneError::AppNotInstalled(name.clone(), "latest".to_string()),
                 )?
-            }
-            .ok_or(CyreneError::AppNotInstalled(
-                app_install_opts.name.clone(),
-                app_install_opts.version.clone(),
-            ))?;
-            let mut transaction = TransactionExecutor::new(actions);
-            transaction.add(TransactionCommands::Link {
-                app: app_install_opts.name.clone(),
-                version: version.clone(),
-                overwrite: true,
-            });
-            transaction.add(TransactionCommands::LockfileUpdate {
-                app: app_install_opts.name.clone(),
-                version: Some(version.clone()),
-            });
-            transaction.execute().await?;
-            Ok(())
+            } else {
+                app_use_opts.version.unwrap_or_default()
+            };
+            app_link(actions, &name, &version).await
         }
         Commands::Unlink(app_install_opts) => {
+            let name = actions.canonical_app_name(&app_install_opts.name);
             let mut transaction = TransactionExecutor::new(actions);
-            transaction.add(TransactionCommands::Unlink {
-                app: app_install_opts.name.clone(),
-            });
+            transaction.add(TransactionCommands::Unlink { app: name });
             transaction.execute().await?;
             Ok(())
         }
         Commands::List(app_version_opts) => {
+            actions.remove_empty_installation_roots()?;
             let lockfile_versions = actions.get_app_version_map()?;
-            let apps: Vec<_> = actions
+            let mut apps: Vec<_> = actions
                 .list_apps()?
                 .iter()
                 .flat_map(|f| {
@@ -469,79 +897,870 @@ async fn start() -> Result<(), CyreneError> {
                     versions
                 })
                 .collect();
+            apps.sort_by(|a, b| {
+                a.name.cmp(&b.name).then_with(|| {
+                    CyreneVersion::parse(&b.version).cmp(&CyreneVersion::parse(&a.version))
+                })
+            });
+            if apps.is_empty() {
+                outputln!(
+                    "{}",
+                    style("No apps installed yet — try cyrene install <name>").fg(Color::Yellow)
+                );
+                return Ok(());
+            }
 
-            tables::cyrene_app_versions_all(&apps, app_version_opts.long);
+            if app_version_opts.flat {
+                tables::cyrene_app_versions_flat(&apps);
+            } else {
+                tables::cyrene_app_versions_all(&apps, app_version_opts.long);
+            }
 
             Ok(())
         }
         Commands::Versions(app_version_opts) => {
-            let versions: Vec<(String, String)> = actions
-                .versions(&app_version_opts.name)
-                .await?
-                .iter()
-                .map(|f| (app_version_opts.name.clone(), f.to_string()))
-                .collect();
+            if app_version_opts.latest {
+                let mut versions: Vec<(String, String)> = Vec::new();
+                for name in app_version_opts.apps.iter() {
+                    versions.push((name.clone(), actions.get_latest_version(name).await?));
+                }
 
-            tables::cyrene_app_versions(&versions, app_version_opts.long);
+                tables::cyrene_app_versions_latest(&versions, app_version_opts.long);
+            } else if let [name] = app_version_opts.apps.as_slice() {
+                let versions: Vec<(String, String)> = actions
+                    .versions(name)
+                    .await?
+                    .iter()
+                    .map(|f| (name.clone(), f.to_string()))
+                    .collect();
+
+                tables::cyrene_app_versions(&versions, app_version_opts.long);
+            } else {
+                for name in app_version_opts.apps.iter() {
+                    let versions: Vec<(String, String)> = actions
+                        .versions(name)
+                        .await?
+                        .iter()
+                        .map(|f| (name.clone(), f.to_string()))
+                        .collect();
+
+                    tables::cyrene_app_versions_grouped(name, &versions, app_version_opts.long);
+                }
+            }
 
             Ok(())
         }
         Commands::Refresh(app_version_opts) => {
             if let Some(name) = app_version_opts.name {
-                println!(
+                outputln!(
                     "Updating versions database for {}",
                     style(&name).fg(Color::Color256(219)).bold()
                 );
                 actions.update_versions(&name).await
             } else {
                 let list_apps = actions.list_apps()?;
+                let mut failures: Vec<(String, CyreneError)> = Vec::new();
                 for name in list_apps.iter() {
-                    println!(
+                    outputln!(
                         "Updating versions database for {}",
                         style(&name).fg(Color::Color256(219)).bold()
                     );
-                    actions.update_versions(name).await?;
+                    match actions.update_versions(name).await {
+                        Ok(()) => {}
+                        Err(e) if app_version_opts.keep_going => failures.push((name.clone(), e)),
+                        Err(e) => return Err(e),
+                    }
                 }
-                Ok(())
+                report_failures(&failures)
             }
         }
         Commands::Load(app_load_opts) => {
-            let mut transactions = TransactionExecutor::new(actions.clone());
+            let mut transactions =
+                TransactionExecutor::new(actions.clone()).with_timings(app_load_opts.timings);
             if app_load_opts.default {
                 let txs = actions.load_lockfile(None).await?;
                 for tx in txs {
                     transactions.add(tx);
                 }
             } else {
-                let lockfile_path = if let Some(path) = app_load_opts.lockfile {
-                    PathBuf::from(path)
-                } else {
-                    PathBuf::from("cyrene.lock")
-                };
-                if !fs::exists(&lockfile_path)
-                    .map_err(|e| CyreneError::LockfileLocalRead(lockfile_path.clone(), e))?
-                {
-                    return Err(CyreneError::LockfileNotFoundError(lockfile_path.clone()));
+                let mut lockfile_paths: Vec<PathBuf> =
+                    app_load_opts.lockfiles.iter().map(PathBuf::from).collect();
+                if lockfile_paths.is_empty() {
+                    lockfile_paths.push(PathBuf::from("cyrene.lock"));
+                }
+                for lockfile_path in &lockfile_paths {
+                    if !fs::exists(lockfile_path)
+                        .map_err(|e| CyreneError::LockfileLocalRead(lockfile_path.clone(), e))?
+                    {
+                        return Err(CyreneError::LockfileNotFoundError(lockfile_path.clone()));
+                    }
                 }
 
-                let txs = actions.load_lockfile(None).await?;
+                let txs = actions.load_lockfile(Some(&lockfile_paths)).await?;
                 for tx in txs {
                     transactions.add(tx);
                 }
             }
             transactions.execute().await?;
 
+            Ok(())
+        }
+        Commands::Check => {
+            let versions = actions.check_lockfile().await?;
+            if versions.is_empty() {
+                outputln!("{}", style("Lockfile is empty").fg(Color::Yellow));
+                return Ok(());
+            }
+            outputln!();
+            tables::cyrene_lockfile_check(&versions);
+            let unavailable = versions.iter().filter(|f| !f.2).count();
+            if unavailable > 0 {
+                return Err(CyreneError::LockfileUnavailable(unavailable));
+            }
+            outputln!("{}", style("All versions available").fg(Color::Green));
+
+            Ok(())
+        }
+        Commands::Outdated(app_outdated_opts) => {
+            let mut outdated: Vec<AppVersionUpgradeAction> = Vec::new();
+            for name in actions.get_app_version_map()?.into_keys() {
+                let app = AppVersion {
+                    name,
+                    version: None,
+                };
+                let app_action =
+                    app_upgrade_action(&actions, app, app_outdated_opts.refresh).await?;
+                if app_action.old_version.ne(&app_action.new_version) {
+                    outdated.push(app_action);
+                }
+            }
+            if app_outdated_opts.json {
+                let outdated = outdated
+                    .iter()
+                    .map(|app_action| {
+                        Ok(serde_json::json!({
+                            "name": app_action.name,
+                            "current": app_action.old_version,
+                            "latest": app_action.new_version,
+                            "range": actions.find_version_range(&app_action.name)?,
+                        }))
+                    })
+                    .collect::<Result<Vec<_>, CyreneError>>()?;
+                let result = serde_json::json!({
+                    "outdated": outdated,
+                });
+                println!("{}", result);
+            } else if outdated.is_empty() {
+                outputln!("{}", style("All apps are up to date").fg(Color::Green));
+            } else {
+                outputln!();
+                tables::cyrene_app_upgrade(&outdated);
+            }
+            if app_outdated_opts.exit_code && !outdated.is_empty() {
+                return Err(CyreneError::AppsOutdated(outdated.len()));
+            }
+
+            Ok(())
+        }
+        Commands::Plugin(app_plugin_opts) => match app_plugin_opts.command {
+            PluginCommands::Show(plugin_show_opts) => {
+                if plugin_show_opts.path {
+                    resultln!("{}", actions.app_path(&plugin_show_opts.name).display());
+                } else {
+                    resultln!("{}", actions.read_app(&plugin_show_opts.name)?.trim_end());
+                }
+
+                Ok(())
+            }
+            PluginCommands::Versions(plugin_versions_opts) => {
+                let app_config = actions.load_app(&plugin_versions_opts.name)?;
+                let versions: Vec<(String, String)> = actions
+                    .fetch_versions(&plugin_versions_opts.name)
+                    .await?
+                    .iter()
+                    .map(|f| (plugin_versions_opts.name.clone(), f.to_string()))
+                    .collect();
+
+                tables::cyrene_app_versions(&versions, plugin_versions_opts.long);
+                outputln!();
+                outputln!(
+                    "Fetched {} versions",
+                    style(versions.len()).fg(Color::Green).bold()
+                );
+                if app_config.settings.semver {
+                    let non_semver = versions
+                        .iter()
+                        .filter(|f| {
+                            matches!(CyreneVersion::parse(&f.1), CyreneVersion::NonSemver(_))
+                        })
+                        .count();
+                    if non_semver > 0 {
+                        outputln!(
+                            "{} versions are not valid semver and will be ignored",
+                            style(non_semver).fg(Color::Yellow).bold()
+                        );
+                    }
+                }
+
+                Ok(())
+            }
+            PluginCommands::Add(plugin_add_opts) => {
+                let plugin_path = actions
+                    .add_plugin(
+                        &plugin_add_opts.name,
+                        plugin_add_opts.refresh,
+                        plugin_add_opts.force,
+                    )
+                    .await?;
+                outputln!(
+                    "Added plugin {} to {}",
+                    style(&plugin_add_opts.name).fg(Color::Color256(219)).bold(),
+                    plugin_path.display()
+                );
+
+                Ok(())
+            }
+            PluginCommands::TestLink(plugin_test_link_opts) => {
+                let links = actions
+                    .test_link(&plugin_test_link_opts.name, &plugin_test_link_opts.version)?;
+                tables::cyrene_test_links(&links);
+                let missing = links.iter().filter(|f| !f.target_exists).count();
+                if missing > 0 {
+                    return Err(CyreneError::IssuesFound(missing));
+                }
+                outputln!("{}", style("All binaries linked").fg(Color::Green));
+
+                Ok(())
+            }
+            PluginCommands::Verify => {
+                let results = actions.verify_plugins()?;
+                let mut failed = 0;
+                for (path, error) in &results {
+                    match error {
+                        Some(e) => {
+                            failed += 1;
+                            resultln!(
+                                "{} {}: {}",
+                                style("Failed").fg(Color::Red).bold(),
+                                path.display(),
+                                e
+                            );
+                        }
+                        None => {
+                            resultln!("{} {}", style("OK").fg(Color::Green).bold(), path.display())
+                        }
+                    }
+                }
+                outputln!();
+                outputln!(
+                    "{} plugins passed, {} failed",
+                    style(results.len() - failed).fg(Color::Green).bold(),
+                    style(failed).fg(Color::Red).bold()
+                );
+                if failed > 0 {
+                    return Err(CyreneError::PluginsFailed(failed));
+                }
+
+                Ok(())
+            }
+        },
+        Commands::Provides(app_provides_opts) => {
+            let providers = actions.find_binary_providers(&app_provides_opts.binary)?;
+            if providers.is_empty() {
+                outputln!(
+                    "{}",
+                    style(format!(
+                        "No linked apps provide {}",
+                        app_provides_opts.binary
+                    ))
+                    .fg(Color::Yellow)
+                );
+            } else {
+                outputln!();
+                tables::cyrene_binary_providers(&app_provides_opts.binary, &providers);
+            }
+
+            Ok(())
+        }
+        Commands::Freeze(app_freeze_opts) => {
+            let current_versions = actions.get_app_version_map()?;
+            let versions = actions.freeze_lockfile().await?;
+            for (name, version) in versions.iter() {
+                if let Some(current_version) = current_versions.get(name)
+                    && !current_version.eq(version)
+                {
+                    outputln!(
+                        "Pinning {} {} to version {}",
+                        style(name).fg(Color::Color256(219)).bold(),
+                        style(current_version).fg(Color::Yellow).bold(),
+                        style(version).fg(Color::Green).bold(),
+                    );
+                }
+            }
+            let file = app_freeze_opts.file.map(PathBuf::from);
+            actions.write_lockfile(file.as_deref(), versions)?;
+
+            Ok(())
+        }
+        Commands::Sync => {
+            let mut app_actions: Vec<AppVersionAction> = Vec::new();
+            for (name, version) in actions.get_app_version_map()? {
+                if !actions.is_version_installed(&name, &version)? {
+                    debug!(
+                        "App version {} for plugin {} is not installed, skipping",
+                        version, name
+                    );
+                    continue;
+                }
+                if !actions.is_version_linked(&name, &version)? {
+                    app_actions.push(AppVersionAction { name, version });
+                }
+            }
+            if !app_actions.is_empty() {
+                outputln!();
+                tables::cyrene_app_relink(&app_actions);
+                outputln!();
+
+                let mut transaction = TransactionExecutor::new(actions.clone());
+                for app_action in app_actions.iter() {
+                    transaction.add(TransactionCommands::Link {
+                        app: app_action.name.clone(),
+                        version: app_action.version.clone(),
+                        overwrite: true,
+                    });
+                }
+                transaction.execute().await?;
+            } else {
+                outputln!("{}", style("No action needed").fg(console::Color::Green));
+            }
+
+            Ok(())
+        }
+        Commands::ReinstallAll => {
+            let app_actions: Vec<AppVersionAction> = actions
+                .get_app_version_map()?
+                .into_iter()
+                .map(|(name, version)| AppVersionAction { name, version })
+                .collect();
+            if app_actions.is_empty() {
+                outputln!("{}", style("Lockfile is empty").fg(Color::Yellow));
+                return Ok(());
+            }
+            outputln!();
+            tables::cyrene_app_reinstall(&app_actions);
+            outputln!();
+            if !confirm(
+                style("reinstallation").fg(Color::Red).bold(),
+                yes,
+                default_confirm,
+            )? {
+                outputln!("{}", style("Aborted").fg(console::Color::Red));
+                return Ok(());
+            }
+
+            // Each app gets its own transaction, so one failure does not stop the others
+            let mut reinstalled: Vec<&AppVersionAction> = Vec::new();
+            let mut failures: Vec<(String, CyreneError)> = Vec::new();
+            for app_action in app_actions.iter() {
+                let mut transaction = TransactionExecutor::new(actions.clone());
+                let app = app_action.name.clone();
+                let version = app_action.version.clone();
+                if actions.is_version_installed(&app, &version)? {
+                    transaction.add(TransactionCommands::Reinstall {
+                        app: app.clone(),
+                        version: version.clone(),
+                    });
+                } else {
+                    transaction.add(TransactionCommands::Install {
+                        app: app.clone(),
+                        version: version.clone(),
+                    });
+                }
+                transaction.add(TransactionCommands::Link {
+                    app,
+                    version,
+                    overwrite: true,
+                });
+                match transaction.execute().await {
+                    Ok(_) => reinstalled.push(app_action),
+                    Err(e) => failures.push((app_action.name.clone(), e)),
+                }
+            }
+            outputln!();
+            for app_action in reinstalled {
+                outputln!(
+                    "{} {} version {}",
+                    style("Reinstalled").fg(Color::Green).bold(),
+                    style(&app_action.name).fg(Color::Color256(219)).bold(),
+                    style(&app_action.version).fg(Color::Green),
+                );
+            }
+            report_failures(&failures)
+        }
+        Commands::Cache(app_cache_opts) => match app_cache_opts.command {
+            CacheCommands::Show(cache_show_opts) => {
+                let versions = actions.cached_versions(&cache_show_opts.name)?;
+                if cache_show_opts.json {
+                    let result = serde_json::json!({
+                        "name": cache_show_opts.name,
+                        "versions": versions,
+                    });
+                    println!("{}", result);
+                    return Ok(());
+                }
+                if versions.is_empty() {
+                    outputln!(
+                        "{}",
+                        style(format!("No cached versions for {}", cache_show_opts.name))
+                            .fg(Color::Yellow)
+                    );
+                    return Ok(());
+                }
+
+                let versions: Vec<(String, String)> = versions
+                    .into_iter()
+                    .map(|f| (cache_show_opts.name.clone(), f))
+                    .collect();
+                tables::cyrene_app_versions(&versions, false);
+                outputln!();
+                outputln!(
+                    "{} cached versions",
+                    style(versions.len()).fg(Color::Green).bold()
+                );
+
+                Ok(())
+            }
+            CacheCommands::Prune => {
+                let removed = actions.prune_version_cache()?;
+                for name in removed.iter() {
+                    outputln!(
+                        "Removed cached versions of {}",
+                        style(name).fg(Color::Color256(219)).bold()
+                    );
+                }
+                outputln!(
+                    "{} cache entries removed",
+                    style(removed.len()).fg(Color::Green).bold()
+                );
+
+                Ok(())
+            }
+        },
+        Commands::Lockfile(app_lockfile_opts) => match app_lockfile_opts.command {
+            LockfileCommands::Show(lockfile_show_opts) => {
+                let loaded_lockfiles = actions.get_loaded_lockfiles()?;
+                let versions = actions.get_lockfile_entries()?;
+                if lockfile_show_opts.json {
+                    let result = serde_json::json!({
+                        "loaded_lockfiles": loaded_lockfiles,
+                        "versions": versions,
+                    });
+                    println!("{}", result);
+                    return Ok(());
+                }
+
+                if loaded_lockfiles.is_empty() {
+                    resultln!("# Using global lockfile");
+                } else {
+                    for loaded_lockfile in loaded_lockfiles {
+                        resultln!("# Loaded lockfile: {}", loaded_lockfile);
+                    }
+                }
+                let lockfile = CyreneLockfile {
+                    versions,
+                    ..Default::default()
+                };
+                let lockfile_write =
+                    toml::ser::to_string(&lockfile).map_err(CyreneError::LockfileSerialize)?;
+                resultln!("{}", lockfile_write.trim_end());
+
+                Ok(())
+            }
+            LockfileCommands::Fix => {
+                let (lockfile_path, changes) = actions.fix_lockfile()?;
+                if changes.is_empty() {
+                    outputln!("{}", style("No action needed").fg(console::Color::Green));
+                    return Ok(());
+                }
+                for (name, version, normalized) in changes.iter() {
+                    match normalized {
+                        Some(normalized) => outputln!(
+                            "Normalized {} {} to {}",
+                            style(name).fg(Color::Color256(219)).bold(),
+                            style(version).fg(Color::Yellow).bold(),
+                            style(normalized).fg(Color::Green).bold(),
+                        ),
+                        None => outputln!(
+                            "Dropped {} {}, plugin not found",
+                            style(name).fg(Color::Color256(219)).bold(),
+                            style(version).fg(Color::Yellow).bold(),
+                        ),
+                    }
+                }
+                outputln!();
+                outputln!("Updated {}", lockfile_path.display());
+
+                Ok(())
+            }
+        },
+        Commands::Shellenv(app_shellenv_opts) => {
+            println!(
+                "{}",
+                shell::path_snippet(app_shellenv_opts.shell, &actions.exe_dir())
+            );
+
+            Ok(())
+        }
+        Commands::Migrate => {
+            let migrated = actions.migrate_layout()?;
+            if migrated.is_empty() {
+                outputln!("{}", style("No action needed").fg(console::Color::Green));
+                return Ok(());
+            }
+            for (name, version) in migrated.iter() {
+                outputln!(
+                    "Moved {} version {}",
+                    style(name).fg(Color::Color256(219)).bold(),
+                    style(version).fg(Color::Green).bold(),
+                );
+            }
+
+            // Links still point to the old locations
+            let mut transaction = TransactionExecutor::new(actions.clone());
+            for (name, version) in actions.get_app_version_map()? {
+                if migrated.contains(&(name.clone(), version.clone())) {
+                    transaction.add(TransactionCommands::Link {
+                        app: name,
+                        version,
+                        overwrite: true,
+                    });
+                }
+            }
+            transaction.execute().await?;
+
+            Ok(())
+        }
+        Commands::Info(app_info_opts) => {
+            let name = &actions.canonical_app_name(&app_info_opts.name);
+            let app_config = actions.load_app(name)?;
+            let metadata = &app_config.metadata;
+            let fields = [
+                ("Description", &metadata.description),
+                ("Homepage", &metadata.homepage),
+                ("Repository", &metadata.repo),
+                ("Minimum cyrene version", &metadata.min_cyrene_version),
+            ];
+            resultln!("{}", style(name).fg(Color::Color256(219)).bold());
+            for (label, value) in fields {
+                if let Some(value) = value {
+                    resultln!("{}: {}", style(label).bold(), value);
+                }
+            }
+
+            let installed: Vec<String> = actions
+                .list_installed_app_versions(name)?
+                .into_iter()
+                .map(|f| f.1)
+                .collect();
+            let installed = if installed.is_empty() {
+                style("none".to_string()).fg(Color::Yellow)
+            } else {
+                style(installed.join(", ")).fg(Color::Green)
+            };
+            resultln!("{}: {}", style("Installed versions").bold(), installed);
+            let linked = match actions.get_app_version_map()?.get(name) {
+                Some(version) => style(version.to_string()).fg(Color::Green).bold(),
+                None => style("none".to_string()).fg(Color::Yellow),
+            };
+            resultln!("{}: {}", style("Linked version").bold(), linked);
+
+            Ok(())
+        }
+        Commands::Explain(app_explain_opts) => {
+            let name = actions.canonical_app_name(&app_explain_opts.name);
+            let steps = actions.explain(&name, &app_explain_opts.version)?;
+            tables::cyrene_app_plan(&name, &app_explain_opts.version, &steps);
+
+            Ok(())
+        }
+        Commands::Search(app_search_opts) => {
+            let plugins = actions
+                .search_registry(&app_search_opts.query, app_search_opts.refresh)
+                .await?;
+            if plugins.is_empty() {
+                outputln!(
+                    "{}",
+                    style(format!("No plugins matching {}", app_search_opts.query))
+                        .fg(Color::Yellow)
+                );
+                return Ok(());
+            }
+            tables::cyrene_registry_search(&app_search_opts.query, &plugins);
+
+            Ok(())
+        }
+        Commands::Linked => {
+            let linked = actions.list_linked_binaries()?;
+            if linked.is_empty() {
+                outputln!("{}", style("No binaries linked").fg(Color::Yellow));
+                return Ok(());
+            }
+            tables::cyrene_linked_binaries(&linked);
+            let dangling = linked.iter().filter(|f| !f.valid).count();
+            if dangling > 0 {
+                outputln!();
+                outputln!(
+                    "{} links are dangling",
+                    style(dangling).fg(Color::Yellow).bold()
+                );
+            }
+
+            Ok(())
+        }
+        Commands::Links(app_links_opts) => {
+            let links = actions.list_lockfile_links()?;
+            if app_links_opts.json {
+                let links: Vec<_> = links
+                    .iter()
+                    .map(|f| {
+                        serde_json::json!({
+                            "binary": f.binary,
+                            "name": f.app,
+                            "version": f.version,
+                            "path": f.path,
+                            "target": f.target,
+                        })
+                    })
+                    .collect();
+                let result = serde_json::json!({
+                    "links": links,
+                });
+                println!("{}", result);
+                return Ok(());
+            }
+            if links.is_empty() {
+                outputln!("{}", style("No apps in the lockfile").fg(Color::Yellow));
+                return Ok(());
+            }
+            tables::cyrene_app_links(&links);
+
+            Ok(())
+        }
+        Commands::Conflicts => {
+            let conflicts = actions.find_binary_conflicts()?;
+            if conflicts.is_empty() {
+                outputln!("{}", style("No conflicting binaries").fg(Color::Green));
+                return Ok(());
+            }
+            tables::cyrene_binary_conflicts(&conflicts);
+
+            Ok(())
+        }
+        Commands::Exec(app_exec_opts) => {
+            let name = actions.canonical_app_name(&app_exec_opts.name);
+            let binary = actions.resolve_binary(&name, Some(&app_exec_opts.binary))?;
+            let error = process::Command::new(&binary)
+                .args(&app_exec_opts.args)
+                .exec();
+
+            Err(CyreneError::AppExec(binary, error))
+        }
+        Commands::Which(app_which_opts) => {
+            let name = actions.canonical_app_name(&app_which_opts.name);
+            let binary = actions.resolve_binary(&name, app_which_opts.binary.as_deref())?;
+            println!("{}", binary.display());
+
+            Ok(())
+        }
+        Commands::Run(app_run_opts) => {
+            let name = actions.canonical_app_name(&app_run_opts.name);
+            let mut command =
+                actions.action_command(&name, &app_run_opts.action, &app_run_opts.args)?;
+            let error = command.exec();
+
+            Err(CyreneError::AppExec(
+                PathBuf::from(command.get_program()),
+                error,
+            ))
+        }
+        Commands::Doctor(app_doctor_opts) => {
+            actions.remove_empty_installation_roots()?;
+            let issues = actions.diagnose()?;
+            if app_doctor_opts.json {
+                let result = serde_json::json!({
+                    "issues": issues,
+                });
+                println!("{}", result);
+            } else if issues.is_empty() {
+                outputln!("{}", style("No issues found").fg(Color::Green));
+            } else {
+                for issue in issues.iter() {
+                    resultln!("{} {}", style("Issue:").fg(Color::Yellow).bold(), issue);
+                }
+            }
+            if !issues.is_empty() {
+                return Err(CyreneError::IssuesFound(issues.len()));
+            }
+
+            Ok(())
+        }
+        Commands::CompleteVersions(app_complete_versions_opts) => {
+            // Completion must stay fast and quiet, so never fetch or report errors
+            let versions = actions
+                .cached_versions(&app_complete_versions_opts.name)
+                .unwrap_or_default();
+            for version in versions {
+                println!("{}", version);
+            }
+
+            Ok(())
+        }
+        Commands::Du(app_du_opts) => {
+            let usage = actions.disk_usage()?;
+            if usage.is_empty() {
+                outputln!(
+                    "{}",
+                    style("No apps installed yet — try cyrene install <name>").fg(Color::Yellow)
+                );
+                return Ok(());
+            }
+            if app_du_opts.by_version {
+                tables::cyrene_disk_usage_by_version(&usage);
+            } else {
+                let mut app_usage: BTreeMap<String, u64> = BTreeMap::new();
+                for (name, _, size) in &usage {
+                    *app_usage.entry(name.clone()).or_default() += size;
+                }
+                let mut app_usage: Vec<(String, u64)> = app_usage.into_iter().collect();
+                app_usage.sort_by_key(|f| std::cmp::Reverse(f.1));
+                tables::cyrene_disk_usage(&app_usage);
+            }
+            let total: u64 = usage.iter().map(|f| f.2).sum();
+            outputln!();
+            resultln!(
+                "{}: {}",
+                style("Total").bold(),
+                style(tables::format_size(total)).fg(Color::Green).bold()
+            );
+
+            Ok(())
+        }
+        Commands::Snapshot(app_snapshot_opts) => {
+            let mut installed = BTreeMap::new();
+            for name in actions.list_apps()? {
+                let versions = actions
+                    .list_installed_app_versions(&name)?
+                    .into_iter()
+                    .map(|f| f.1)
+                    .collect();
+                installed.insert(name, versions);
+            }
+            let snapshot = CyreneSnapshot {
+                config: (*config).clone(),
+                versions: actions.get_app_version_map()?,
+                installed,
+            };
+            let snapshot_path = PathBuf::from(&app_snapshot_opts.file);
+            snapshot.write(&snapshot_path)?;
+            outputln!("Saved snapshot to {}", snapshot_path.display());
+
+            Ok(())
+        }
+        Commands::Restore(app_snapshot_opts) => {
+            let snapshot = CyreneSnapshot::read(&PathBuf::from(&app_snapshot_opts.file))?;
+            snapshot.config.save(&config_path)?;
+            outputln!("Restored config to {}", config_path.display());
+
+            let mut transaction = TransactionExecutor::new(actions.clone());
+            let mut to_install = HashSet::new();
+            let linked = snapshot.versions.iter();
+            let installed = snapshot
+                .installed
+                .iter()
+                .flat_map(|(name, versions)| versions.iter().map(move |f| (name, f)));
+            for (name, version) in linked.chain(installed) {
+                if !actions.is_version_installed(name, version)?
+                    && to_install.insert((name.clone(), version.clone()))
+                {
+                    transaction.add(TransactionCommands::Install {
+                        app: name.clone(),
+                        version: version.clone(),
+                    });
+                }
+            }
+            for (name, version) in snapshot.versions {
+                transaction.add(TransactionCommands::Link {
+                    app: name.clone(),
+                    version: version.clone(),
+                    overwrite: true,
+                });
+                transaction.add(TransactionCommands::LockfileUpdate {
+                    app: name,
+                    version: Some(version),
+                });
+            }
+            transaction.execute().await?;
+
             Ok(())
         }
     }
 }
 
+async fn app_link(
+    actions: Arc<CyreneManager>,
+    name: &str,
+    version: &str,
+) -> Result<(), CyreneError> {
+    let app_config = actions.load_app(name)?;
+
+    let version = if !app_config.settings.semver {
+        Some(version.to_string())
+    } else if let CyreneVersion::Semver(_) = CyreneVersion::parse(version) {
+        Some(version.to_string())
+    } else {
+        actions.find_installed_major_release(name, version)?
+    }
+    .ok_or(CyreneError::AppNotInstalled(
+        name.to_string(),
+        version.to_string(),
+    ))?;
+    let mut transaction = TransactionExecutor::new(actions);
+    transaction.add(TransactionCommands::Link {
+        app: name.to_string(),
+        version: version.clone(),
+        overwrite: true,
+    });
+    transaction.add(TransactionCommands::LockfileUpdate {
+        app: name.to_string(),
+        version: Some(version.clone()),
+    });
+    transaction.execute().await?;
+    Ok(())
+}
+
+async fn app_version_not_found(actions: &CyreneManager, name: &str, version: &str) -> CyreneError {
+    if let Ok(versions) = actions.versions(name).await
+        && !versions.is_empty()
+    {
+        let newest_versions: Vec<_> = versions.iter().take(5).map(String::as_str).collect();
+        outputln!(
+            "Newest available versions for {}: {}",
+            style(name).fg(Color::Color256(219)).bold(),
+            style(newest_versions.join(", ")).fg(Color::Green).bold(),
+        );
+    }
+
+    CyreneError::AppVersionNotFound(name.to_string(), version.to_string())
+}
+
 async fn app_upgrade(
     actions: Arc<CyreneManager>,
     app_install_opts: &AppUpgradeOpts,
+    yes: bool,
+    default_confirm: bool,
 ) -> Result<(), CyreneError> {
     let app_to_be_installed: Vec<_> = if let Some(apps) = &app_install_opts.apps {
-        apps.iter().map(AppVersion::from).collect()
+        parse_app_versions(&actions, apps)
     } else {
         actions
             .list_apps()?
@@ -554,67 +1773,36 @@ async fn app_upgrade(
     };
     let mut app_actions: Vec<AppVersionUpgradeAction> = Vec::new();
     let mut app_actions_unneeded: Vec<AppVersionUpgradeAction> = Vec::new();
+    let mut failures: Vec<(String, CyreneError)> = Vec::new();
     for app in app_to_be_installed {
-        let old_version = match &app.version {
-            Some(ver) => actions.find_installed_major_release(&app.name, ver)?,
-            None => actions.find_installed_version(&app.name)?,
-        }
-        .ok_or(CyreneError::AppNotInstalled(
-            app.name.to_string(),
-            match &app.version {
-                Some(ver) => ver.to_string(),
-                None => "latest".to_string(),
-            },
-        ))?;
-        let upgrade_latest = actions.check_upgrade_latest(&app.name)?;
-        let new_version = if upgrade_latest {
-            Some(actions.get_latest_version(&app.name).await?)
-        } else {
-            actions
-                .get_latest_major_release(&app.name, &old_version)
-                .await?
-        }
-        .ok_or(CyreneError::AppVersionNotFound(
-            app.name.clone(),
-            old_version.clone(),
-        ))?;
-        if old_version.eq(&new_version) {
-            app_actions_unneeded.push(AppVersionUpgradeAction {
-                name: app.name,
-                old_version,
-                new_version,
-            })
+        let name = app.name.clone();
+        let app_action = match app_upgrade_action(&actions, app, app_install_opts.refresh).await {
+            Ok(app_action) => app_action,
+            Err(e) if app_install_opts.keep_going => {
+                failures.push((name, e));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if app_action.old_version.eq(&app_action.new_version) {
+            app_actions_unneeded.push(app_action)
         } else {
-            app_actions.push(AppVersionUpgradeAction {
-                name: app.name,
-                old_version,
-                new_version,
-            })
+            app_actions.push(app_action)
         }
     }
     if !app_actions_unneeded.is_empty() {
-        println!();
+        outputln!();
         tables::cyrene_app_upgrade_unneeded(&app_actions_unneeded);
     }
     if !app_actions.is_empty() {
-        println!();
+        outputln!();
         tables::cyrene_app_upgrade(&app_actions);
-        println!();
-        let theme = ColorfulTheme {
-            prompt_style: Style::new().fg(Color::Color256(219)),
-            ..Default::default()
-        };
-        if Confirm::with_theme(&theme)
-            .default(false)
-            .show_default(true)
-            .wait_for_newline(true)
-            .with_prompt(format!(
-                "Proceed with {}?",
-                style("upgrade").fg(Color::Green).bold()
-            ))
-            .interact()
-            .map_err(CyreneError::Interaction)?
-        {
+        outputln!();
+        if confirm(
+            style("upgrade").fg(Color::Green).bold(),
+            yes,
+            default_confirm,
+        )? {
             let mut transactions = TransactionExecutor::new(actions.clone());
             for app_action in app_actions.iter() {
                 transactions.add(TransactionCommands::Install {
@@ -630,17 +1818,169 @@ async fn app_upgrade(
                     app: app_action.name.clone(),
                     version: Some(app_action.new_version.clone()),
                 });
-                transactions.add(TransactionCommands::Remove {
-                    app: app_action.name.clone(),
-                    version: app_action.old_version.clone(),
-                });
+                for version in actions.find_upgrade_prunable_versions(
+                    &app_action.name,
+                    &app_action.old_version,
+                    &app_action.new_version,
+                )? {
+                    transactions.add(TransactionCommands::Remove {
+                        app: app_action.name.clone(),
+                        version,
+                    });
+                }
             }
             transactions.execute().await?;
         } else {
-            println!("{}", style("Aborted").fg(console::Color::Red))
+            outputln!("{}", style("Aborted").fg(console::Color::Red))
         }
     } else {
-        println!("{}", style("No action needed").fg(console::Color::Green));
+        outputln!("{}", style("No action needed").fg(console::Color::Green));
     }
-    Ok(())
+    report_failures(&failures)
+}
+
+async fn app_upgrade_action(
+    actions: &CyreneManager,
+    app: AppVersion,
+    refresh: bool,
+) -> Result<AppVersionUpgradeAction, CyreneError> {
+    if refresh {
+        actions.update_versions(&app.name).await?;
+    }
+    let old_version = match &app.version {
+        Some(ver) => actions.find_installed_major_release(&app.name, ver)?,
+        None => actions.find_installed_version(&app.name)?,
+    }
+    .ok_or(CyreneError::AppNotInstalled(
+        app.name.to_string(),
+        match &app.version {
+            Some(ver) => ver.to_string(),
+            None => "latest".to_string(),
+        },
+    ))?;
+    let upgrade_latest = actions.check_upgrade_latest(&app.name)?;
+    // Honor the version requirement or channel the linked version was installed with
+    let range = match &app.version {
+        Some(_) => None,
+        None => actions.find_version_range(&app.name)?,
+    };
+    let new_version = if let Some(range) = &range {
+        actions.resolve_version_spec(&app.name, range).await?
+    } else if upgrade_latest {
+        Some(actions.get_latest_version(&app.name).await?)
+    } else {
+        actions
+            .get_latest_major_release(&app.name, &old_version)
+            .await?
+    };
+    let Some(new_version) = new_version else {
+        return Err(app_version_not_found(actions, &app.name, &old_version).await);
+    };
+
+    Ok(AppVersionUpgradeAction {
+        name: app.name,
+        old_version,
+        new_version,
+    })
+}
+
+/// Parse `name@version` arguments, matching app names to plugins regardless of case
+fn parse_app_versions(actions: &CyreneManager, apps: &[String]) -> Vec<AppVersion> {
+    apps.iter()
+        .map(AppVersion::from)
+        .map(|app| AppVersion {
+            name: actions.canonical_app_name(&app.name),
+            version: app.version,
+        })
+        .collect()
+}
+
+/// Read app specs from a file, skipping blank lines and `#` comments
+fn read_app_list(path: &Path) -> Result<Vec<String>, CyreneError> {
+    let app_list =
+        fs::read_to_string(path).map_err(|e| CyreneError::AppListRead(path.to_path_buf(), e))?;
+
+    Ok(app_list
+        .lines()
+        .map(str::trim)
+        .filter(|f| !f.is_empty() && !f.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Times to ask for confirmation before giving up on unrecognized responses
+const CONFIRM_ATTEMPTS: usize = 3;
+
+/// Ask whether to proceed with `action`, or proceed right away with `--yes`.
+///
+/// `default` is the answer when only Enter is pressed.
+fn confirm(action: StyledObject<&str>, yes: bool, default: bool) -> Result<bool, CyreneError> {
+    if yes {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(CyreneError::NoTerminal);
+    }
+    let theme = ColorfulTheme {
+        prompt_style: Style::new().fg(Color::Color256(219)),
+        ..Default::default()
+    };
+    let prompt = format!(
+        "Proceed with {}? {}",
+        action,
+        if default { "[Y/n]" } else { "[y/N]" }
+    );
+    // `Confirm` ignores keys it does not recognize, so responses are read as text to notice typos
+    let mut attempts = 1;
+    loop {
+        let response: String = Input::with_theme(&theme)
+            .with_prompt(&prompt)
+            .allow_empty(true)
+            .interact_text()
+            .map_err(CyreneError::Interaction)?;
+        match parse_confirmation(&response, default) {
+            Some(proceed) => return Ok(proceed),
+            None if attempts < CONFIRM_ATTEMPTS => {
+                eprintln!(
+                    "{} unrecognized response ({}), please try again",
+                    style("Warning:").fg(Color::Yellow).bold(),
+                    response.trim()
+                );
+                attempts += 1;
+            }
+            None => {
+                return Err(CyreneError::UnrecognizedResponse(
+                    response.trim().to_string(),
+                ));
+            }
+        }
+    }
+}
+
+/// Read a yes or no response, an empty response picks `default`
+fn parse_confirmation(response: &str, default: bool) -> Option<bool> {
+    match response.trim().to_lowercase().as_str() {
+        "" => Some(default),
+        "y" | "yes" => Some(true),
+        "n" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Print apps that failed with `--keep-going`
+fn report_failures(failures: &[(String, CyreneError)]) -> Result<(), CyreneError> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    outputln!();
+    for (name, e) in failures {
+        outputln!(
+            "{} {}: {}",
+            style("Failed").fg(Color::Red).bold(),
+            style(name).fg(Color::Color256(219)).bold(),
+            e
+        );
+    }
+
+    Err(CyreneError::AppsFailed(failures.len()))
 }
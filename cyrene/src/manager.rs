@@ -1,31 +1,358 @@
 use std::{
-    collections::BTreeMap,
-    fs,
-    path::{Path, PathBuf},
-    sync::Arc,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs, io,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::{Component, Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
+use console::{Color, style};
 use log::debug;
 
 use crate::{
-    app::CyreneApp, dirs::CyreneDirs, errors::CyreneError, lockfile::CyreneLockfileManager,
-    transaction::TransactionCommands, util, version::CyreneVersion,
+    app::{AppPlanStep, CyreneApp},
+    app_module::sources::{self, Cancellation},
+    config::{CyreneConfig, LinkMode, VersionSuffix},
+    dirs::CyreneDirs,
+    doctor::CyreneIssue,
+    errors::CyreneError,
+    install_lock::CyreneInstallLocks,
+    lockfile::{CyreneLockfileEntry, CyreneLockfileFix, CyreneLockfileManager},
+    registry::{CyreneRegistryEntry, CyreneRegistryManager},
+    transaction::TransactionCommands,
+    util,
+    version::CyreneVersion,
     versions_cache::CyreneVersionCacheManager,
 };
 
+/// A binary in the install directory linked by cyrene
+pub struct CyreneLinkedBinary {
+    pub binary: String,
+    pub app: String,
+    pub version: String,
+    pub target: PathBuf,
+    /// Whether the link target exists
+    pub valid: bool,
+}
+
+/// A binary of a version in the lockfile, and where it is linked
+pub struct CyreneAppLink {
+    pub binary: String,
+    pub app: String,
+    pub version: String,
+    /// Link in the install directory
+    pub path: PathBuf,
+    pub target: PathBuf,
+}
+
+/// A binary that more than one installed app can provide
+pub struct CyreneBinaryConflict {
+    pub binary: String,
+    /// Apps providing the binary, with the version that was checked
+    pub providers: Vec<(String, String)>,
+    /// App the binary is currently linked to
+    pub linked: Option<String>,
+}
+
+/// A binary linked by a test link
+pub struct CyreneTestLink {
+    pub binary: String,
+    pub target: PathBuf,
+    pub created: bool,
+    pub target_exists: bool,
+}
+
 pub struct CyreneManager {
+    config: Arc<CyreneConfig>,
     dirs: Arc<CyreneDirs>,
     lockfile: Box<CyreneLockfileManager>,
     version_cache: Box<CyreneVersionCacheManager>,
+    registry: Box<CyreneRegistryManager>,
+    install_locks: CyreneInstallLocks,
+    no_cache: bool,
+    shadow_warned: Mutex<HashSet<String>>,
+}
+
+fn link_file(link_mode: LinkMode, canonical_path: &Path, exe_path: &Path) -> io::Result<()> {
+    match link_mode {
+        LinkMode::Symlink => symlink::symlink_file(canonical_path, exe_path),
+        LinkMode::Hardlink => fs::hard_link(canonical_path, exe_path),
+        LinkMode::Copy => fs::copy(canonical_path, exe_path).map(|_| ()),
+    }
+}
+
+/// Link with `link`, using `link_fallback` when a hardlink would cross filesystems
+fn link_file_with_fallback(
+    link_mode: LinkMode,
+    link_fallback: LinkMode,
+    canonical_path: &Path,
+    exe_path: &Path,
+    link: impl Fn(LinkMode, &Path, &Path) -> io::Result<()>,
+) -> io::Result<()> {
+    match link(link_mode, canonical_path, exe_path) {
+        // Hardlinks cannot cross filesystems
+        Err(e) if link_mode == LinkMode::Hardlink && e.kind() == io::ErrorKind::CrossesDevices => {
+            eprintln!(
+                "{} {} is on another filesystem, using {:?} instead of hardlink",
+                style("Warning:").fg(Color::Yellow).bold(),
+                canonical_path.display(),
+                link_fallback,
+            );
+            link(link_fallback, canonical_path, exe_path)
+        }
+        result => result,
+    }
 }
 
 // Private functions
 impl CyreneManager {
     fn get_app_path(&self, name: &str) -> PathBuf {
-        let mut app_path = self.dirs.plugins_dir.clone();
-        app_path.push(format!("{}.cyrene", name));
+        let app_paths: Vec<PathBuf> = self
+            .dirs
+            .plugin_paths
+            .iter()
+            .map(|f| {
+                let mut app_path = f.clone();
+                app_path.push(format!("{}.cyrene", name));
 
-        app_path
+                app_path
+            })
+            .collect();
+        let mut found_paths = app_paths.iter().filter(|f| f.exists());
+        match found_paths.next() {
+            Some(app_path) => {
+                let mut shadow_warned = self.shadow_warned.lock().unwrap();
+                if !shadow_warned.insert(name.to_string()) {
+                    return app_path.clone();
+                }
+                for shadowed_path in found_paths {
+                    eprintln!(
+                        "{} plugin {} at {} is shadowed by {}",
+                        style("Warning:").fg(Color::Yellow).bold(),
+                        style(name).fg(Color::Color256(219)).bold(),
+                        shadowed_path.display(),
+                        app_path.display(),
+                    );
+                }
+                app_path.clone()
+            }
+            None => app_paths.first().unwrap().clone(),
+        }
+    }
+    async fn with_plugin_timeout<T>(
+        &self,
+        name: &str,
+        fut: impl Future<Output = Result<T, CyreneError>>,
+    ) -> Result<T, CyreneError> {
+        match self.config.plugin_timeout {
+            Some(secs) => tokio::time::timeout(Duration::from_secs(secs), fut)
+                .await
+                .map_err(|_| CyreneError::PluginTimeout(name.to_string(), secs))?,
+            None => fut.await,
+        }
+    }
+    /// Run the install of an app, removing the partial installation if it exceeds
+    /// `install_timeout`.
+    ///
+    /// Extraction still running on a blocking thread is stopped through `cancel` before the
+    /// installation is removed.
+    pub async fn with_install_timeout<T>(
+        &self,
+        name: &str,
+        version: &str,
+        cancel: &Cancellation,
+        fut: impl Future<Output = Result<T, CyreneError>>,
+    ) -> Result<T, CyreneError> {
+        let Some(secs) = self.config.install_timeout else {
+            return fut.await;
+        };
+        match tokio::time::timeout(Duration::from_secs(secs), fut).await {
+            Ok(result) => result,
+            Err(_) => {
+                cancel.cancel().await;
+                let installation_path = self.dirs.installation_path(name, version);
+                if installation_path.exists() {
+                    fs::remove_dir_all(&installation_path).map_err(|e| {
+                        CyreneError::AppRemove(name.to_string(), version.to_string(), e)
+                    })?;
+                }
+                Err(CyreneError::InstallTimeout(
+                    name.to_string(),
+                    version.to_string(),
+                    secs,
+                ))
+            }
+        }
+    }
+    /// Directory binaries of an app are linked into, from `bin_overrides` or `install_dir`
+    fn app_exe_dir(&self, name: &str) -> PathBuf {
+        match self.config.bin_override(name) {
+            Some(exe_dir) => exe_dir.to_path_buf(),
+            None => self.dirs.exe_dir.clone(),
+        }
+    }
+    /// Directory holding links to binaries of a single version, used with shims
+    fn versioned_exe_dir(&self, name: &str, version: &str) -> PathBuf {
+        let mut versioned_exe_dir = self.app_exe_dir(name);
+        versioned_exe_dir.push(format!("{}-{}", name, version));
+
+        versioned_exe_dir
+    }
+    /// Link `exe_path` to `canonical_path`, or write a shim there when `shims` is set
+    fn create_link(
+        &self,
+        name: &str,
+        bin_name: &str,
+        canonical_path: &Path,
+        exe_path: &Path,
+        shims: bool,
+    ) -> Result<(), CyreneError> {
+        let link_error = |e| {
+            CyreneError::AppLinkCreate(
+                exe_path.to_string_lossy().to_string(),
+                canonical_path.to_string_lossy().to_string(),
+                e,
+            )
+        };
+        if shims {
+            let current_exe = std::env::current_exe().map_err(CyreneError::ExeCheck)?;
+            let shim = format!(
+                "#!/bin/sh\nexec \"{}\" exec \"{}\" \"{}\" -- \"$@\"\n",
+                current_exe.to_string_lossy(),
+                name,
+                bin_name
+            );
+            fs::write(exe_path, shim).map_err(link_error)?;
+            return fs::set_permissions(exe_path, fs::Permissions::from_mode(0o755))
+                .map_err(link_error);
+        }
+
+        link_file_with_fallback(
+            self.config.link_mode(),
+            self.config.link_fallback(),
+            canonical_path,
+            exe_path,
+            link_file,
+        )
+        .map_err(link_error)
+    }
+    /// Suffix of version-suffixed links to binaries of `version`, if they are enabled
+    fn version_suffix(&self, version: &str) -> Option<String> {
+        match self.config.version_suffix? {
+            VersionSuffix::Major => match CyreneVersion::parse(version) {
+                CyreneVersion::Semver(version) => Some(version.major.to_string()),
+                CyreneVersion::NonSemver(version) => Some(version),
+            },
+            VersionSuffix::Full => Some(version.to_string()),
+        }
+    }
+    /// Link binaries of an installed version under their version-suffixed names, e.g. `node-20`.
+    ///
+    /// These always point straight at the version, even when shims are enabled.
+    fn link_suffixed_binaries(
+        &self,
+        name: &str,
+        version: &str,
+        suffix: &str,
+        overwrite: bool,
+    ) -> Result<(), CyreneError> {
+        let app = self.load_app(name)?;
+        let installation_path = self.dirs.installation_path(name, version);
+        for (bin_name, bin_path) in app.binaries(version)? {
+            let canonical_path = installation_path.join(bin_path);
+            let mut exe_path = self.app_exe_dir(name);
+            exe_path.push(format!("{}-{}", bin_name, suffix));
+            if fs::symlink_metadata(&exe_path).is_ok() {
+                if !overwrite {
+                    continue;
+                }
+                fs::remove_file(&exe_path).map_err(|e| {
+                    CyreneError::AppLinkRemove(exe_path.to_string_lossy().to_string(), e)
+                })?;
+            }
+            debug!(
+                "linking {} to {}",
+                exe_path.to_string_lossy(),
+                canonical_path.to_string_lossy()
+            );
+            self.create_link(name, &bin_name, &canonical_path, &exe_path, false)?;
+        }
+
+        Ok(())
+    }
+    /// Remove version-suffixed links that point to an installed version
+    fn unlink_suffixed_binaries(&self, name: &str, version: &str) -> Result<(), CyreneError> {
+        let Some(suffix) = self.version_suffix(version) else {
+            return Ok(());
+        };
+        let app = self.load_app(name)?;
+        let installation_path = self.dirs.installation_path(name, version);
+        for (bin_name, bin_path) in app.binaries(version)? {
+            let canonical_path = installation_path.join(bin_path);
+            let mut exe_path = self.app_exe_dir(name);
+            exe_path.push(format!("{}-{}", bin_name, suffix));
+            if self.is_linked_to(&exe_path, &canonical_path) {
+                debug!("unlinking {}", exe_path.to_string_lossy());
+                fs::remove_file(&exe_path).map_err(|e| {
+                    CyreneError::AppLinkRemove(exe_path.to_string_lossy().to_string(), e)
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+    /// Whether `exe_path` is a symlink, hardlink or copy of `canonical_path`
+    fn is_linked_to(&self, exe_path: &Path, canonical_path: &Path) -> bool {
+        if let Ok(link_path) = fs::read_link(exe_path) {
+            return link_path.eq(canonical_path);
+        }
+        let (Ok(exe), Ok(canonical)) = (fs::metadata(exe_path), fs::metadata(canonical_path))
+        else {
+            return false;
+        };
+        if exe.dev() == canonical.dev() && exe.ino() == canonical.ino() {
+            return true;
+        }
+
+        exe.len() == canonical.len()
+            && matches!(
+                (fs::read(exe_path), fs::read(canonical_path)),
+                (Ok(exe), Ok(canonical)) if exe == canonical
+            )
+    }
+    /// Binaries that could be linked, by the path they are linked at, with their app, version and
+    /// canonical path
+    fn link_candidates(&self) -> Result<BTreeMap<PathBuf, (String, String, PathBuf)>, CyreneError> {
+        let mut candidates = BTreeMap::new();
+        for link in self.list_lockfile_links()? {
+            candidates.insert(link.path, (link.app, link.version, link.target));
+        }
+        for name in self.list_apps()? {
+            let Ok(app) = self.load_app(&name) else {
+                continue;
+            };
+            for (_, version) in self.list_installed_app_versions(&name)? {
+                let Some(suffix) = self.version_suffix(&version) else {
+                    continue;
+                };
+                let installation_path = self.dirs.installation_path(&name, &version);
+                for (bin_name, bin_path) in app.binaries(&version)? {
+                    candidates.insert(
+                        self.app_exe_dir(&name)
+                            .join(format!("{}-{}", bin_name, suffix)),
+                        (
+                            name.clone(),
+                            version.clone(),
+                            installation_path.join(bin_path),
+                        ),
+                    );
+                }
+            }
+        }
+
+        Ok(candidates)
     }
     fn verify_version_exists(&self, name: &str, version: &str) -> Result<bool, CyreneError> {
         let versions = self.version_cache.get_versions(name)?;
@@ -35,14 +362,43 @@ impl CyreneManager {
 }
 impl CyreneManager {
     pub fn new(
+        config: Arc<CyreneConfig>,
         dirs: Arc<CyreneDirs>,
         lockfile_manager: Box<CyreneLockfileManager>,
         cache_manager: Box<CyreneVersionCacheManager>,
+        registry_manager: Box<CyreneRegistryManager>,
     ) -> Self {
         Self {
+            config,
             dirs,
             lockfile: lockfile_manager,
             version_cache: cache_manager,
+            registry: registry_manager,
+            install_locks: CyreneInstallLocks::default(),
+            no_cache: false,
+            shadow_warned: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Fetch versions on every lookup instead of reading the version cache
+    pub fn with_no_cache(mut self, no_cache: bool) -> Self {
+        self.no_cache = no_cache;
+        self
+    }
+
+    /// Name of the plugin for `name`, matching plugin file names regardless of case
+    pub fn canonical_app_name(&self, name: &str) -> String {
+        match self.dirs.find_plugin_name(name) {
+            Some(canonical_name) if canonical_name.ne(name) => {
+                eprintln!(
+                    "{} using plugin {} for {}",
+                    style("Warning:").fg(Color::Yellow).bold(),
+                    style(&canonical_name).fg(Color::Color256(219)).bold(),
+                    name,
+                );
+                canonical_name
+            }
+            _ => name.to_string(),
         }
     }
 
@@ -51,34 +407,123 @@ impl CyreneManager {
         CyreneApp::from_file(&plugin_path)
     }
 
+    pub async fn search_registry(
+        &self,
+        query: &str,
+        refresh: bool,
+    ) -> Result<Vec<(String, CyreneRegistryEntry)>, CyreneError> {
+        Ok(self.registry.load_index(refresh).await?.search(query))
+    }
+
+    /// Download a plugin from the registry into the plugins directory
+    pub async fn add_plugin(
+        &self,
+        name: &str,
+        refresh: bool,
+        overwrite: bool,
+    ) -> Result<PathBuf, CyreneError> {
+        // The name becomes a file name, so it must not reach outside the plugins directory
+        let mut components = Path::new(name).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(component)), None) if component == name
+        ) {
+            return Err(CyreneError::PluginNameInvalid(name.to_string()));
+        }
+        let mut plugin_path = self.dirs.plugins_dir.clone();
+        plugin_path.push(format!("{}.cyrene", name));
+        if !overwrite
+            && fs::exists(&plugin_path)
+                .map_err(|e| CyreneError::PluginWrite(plugin_path.clone(), e))?
+        {
+            return Err(CyreneError::PluginExists(name.to_string(), plugin_path));
+        }
+        let recipe = self.registry.fetch_plugin(name, refresh).await?;
+        // Refuse to save recipes that can't be loaded
+        CyreneApp::from_str(&recipe)?;
+        fs::write(&plugin_path, recipe)
+            .map_err(|e| CyreneError::PluginWrite(plugin_path.clone(), e))?;
+
+        Ok(plugin_path)
+    }
+
+    pub fn exe_dir(&self) -> PathBuf {
+        self.dirs.exe_dir.clone()
+    }
+
+    pub fn app_path(&self, name: &str) -> PathBuf {
+        self.get_app_path(name)
+    }
+
+    pub fn read_app(&self, name: &str) -> Result<String, CyreneError> {
+        let plugin_path = self.get_app_path(name);
+        fs::read_to_string(&plugin_path).map_err(|e| CyreneError::AppRead(plugin_path, e))
+    }
+
+    /// Parse every plugin, pairing its path with the error it failed with
+    pub fn verify_plugins(&self) -> Result<Vec<(PathBuf, Option<CyreneError>)>, CyreneError> {
+        let plugin_files = self
+            .dirs
+            .plugin_files()
+            .map_err(|e| CyreneError::AppList(self.dirs.plugins_dir.clone(), e))?;
+
+        Ok(plugin_files
+            .into_iter()
+            .map(|f| {
+                let error = CyreneApp::from_file(&f).err();
+                (f, error)
+            })
+            .collect())
+    }
+
     pub fn list_apps(&self) -> Result<Vec<String>, CyreneError> {
-        let installation_root = self.dirs.apps_dir.clone();
-        let list_dirs = fs::read_dir(&installation_root)
-            .map_err(|e| CyreneError::AppList(installation_root.to_path_buf(), e))?;
-        let apps: Vec<_> = list_dirs
-            .filter_map(|p| p.ok())
-            .map(|f| f.path().file_name().unwrap().to_string_lossy().to_string())
-            .collect();
+        self.dirs
+            .installed_apps()
+            .map_err(|e| CyreneError::AppList(self.dirs.apps_dir.clone(), e))
+    }
 
-        Ok(apps)
+    /// Remove installation roots left without versions, e.g. by a failed first install
+    pub fn remove_empty_installation_roots(&self) -> Result<Vec<String>, CyreneError> {
+        self.dirs
+            .remove_empty_installation_roots()
+            .map_err(|e| CyreneError::AppRootRemove(self.dirs.apps_dir.clone(), e))
     }
 
     pub fn get_app_version_map(&self) -> Result<BTreeMap<String, String>, CyreneError> {
         self.lockfile.load_version_map_from_current_lockfile()
     }
 
+    pub fn get_lockfile_entries(
+        &self,
+    ) -> Result<BTreeMap<String, CyreneLockfileEntry>, CyreneError> {
+        self.lockfile.load_entries_from_current_lockfile()
+    }
+
+    /// Drop lockfile entries without a plugin and normalize versions
+    pub fn fix_lockfile(&self) -> Result<CyreneLockfileFix, CyreneError> {
+        self.lockfile
+            .fix_lockfile(|name| self.get_app_path(name).exists())
+    }
+
+    pub fn get_loaded_lockfiles(&self) -> Result<Vec<String>, CyreneError> {
+        self.lockfile.get_loaded_lockfiles()
+    }
+
+    /// Versions required by loaded local lockfiles, with the lockfile requiring each one
+    pub fn get_local_lockfile_versions(
+        &self,
+    ) -> Result<BTreeMap<String, (String, String)>, CyreneError> {
+        self.lockfile.load_local_versions()
+    }
+
     pub fn list_installed_app_versions(
         &self,
         name: &str,
     ) -> Result<Vec<(String, String)>, CyreneError> {
-        let installation_root = self.dirs.installation_root(name);
-        let list_dirs = fs::read_dir(installation_root)
+        let mut a: Vec<String> = self
+            .dirs
+            .installed_versions(name)
             .map_err(|e| CyreneError::AppCheck(name.to_string(), "".to_string(), e))?;
-
-        let mut a: Vec<String> = list_dirs
-            .filter_map(|p| p.ok())
-            .map(|p| p.path().file_name().unwrap().to_string_lossy().to_string())
-            .collect();
         a.sort_by(|a, b| {
             let a = CyreneVersion::parse(a);
             let b = CyreneVersion::parse(b);
@@ -91,18 +536,51 @@ impl CyreneManager {
         Ok(a)
     }
 
+    /// Size in bytes of every installed version, largest first
+    pub fn disk_usage(&self) -> Result<Vec<(String, String, u64)>, CyreneError> {
+        let mut usage = Vec::new();
+        for name in self.list_apps()? {
+            for (name, version) in self.list_installed_app_versions(&name)? {
+                let size = self
+                    .dirs
+                    .installation_size(&name, &version)
+                    .map_err(|e| CyreneError::AppCheck(name.clone(), version.clone(), e))?;
+                usage.push((name, version, size));
+            }
+        }
+        usage.sort_by_key(|f| std::cmp::Reverse(f.2));
+
+        Ok(usage)
+    }
+
+    /// Versions in the cache, without fetching them
+    pub fn cached_versions(&self, name: &str) -> Result<Vec<String>, CyreneError> {
+        self.version_cache.get_versions(name)
+    }
+
+    /// Drop cached versions of apps without a plugin, returning their names
+    pub fn prune_version_cache(&self) -> Result<Vec<String>, CyreneError> {
+        self.version_cache
+            .prune(|name| self.get_app_path(name).exists())
+    }
+
     pub async fn versions(&self, name: &str) -> Result<Vec<String>, CyreneError> {
         let versions = self.version_cache.get_versions(name)?;
-        if versions.is_empty() {
+        if self.no_cache || versions.is_empty() {
             self.update_versions(name).await?;
             return self.version_cache.get_versions(name);
         }
         Ok(versions)
     }
 
-    pub async fn update_versions(&self, name: &str) -> Result<(), CyreneError> {
+    pub async fn fetch_versions(&self, name: &str) -> Result<Vec<String>, CyreneError> {
         let app = self.load_app(name)?;
-        let versions = app.get_versions().await?;
+        self.with_plugin_timeout(name, app.get_versions(&self.config))
+            .await
+    }
+
+    pub async fn update_versions(&self, name: &str) -> Result<(), CyreneError> {
+        let versions = self.fetch_versions(name).await?;
         self.version_cache.update_version_cache(name, versions)?;
         Ok(())
     }
@@ -117,19 +595,10 @@ impl CyreneManager {
         version: &str,
     ) -> Result<Option<String>, CyreneError> {
         let app_config = self.load_app(name)?;
-        let installation_root = self.dirs.installation_root(name);
-        if !fs::exists(&installation_root)
-            .map_err(|e| CyreneError::AppCheck(name.to_string(), version.to_string(), e))?
-        {
-            return Ok(None);
-        }
-        let list_dirs = fs::read_dir(installation_root)
+        let mut a: Vec<String> = self
+            .dirs
+            .installed_versions(name)
             .map_err(|e| CyreneError::AppCheck(name.to_string(), version.to_string(), e))?;
-
-        let mut a: Vec<String> = list_dirs
-            .filter_map(|p| p.ok())
-            .map(|p| p.path().file_name().unwrap().to_string_lossy().to_string())
-            .collect();
         a.sort_by(|a, b| {
             let a = CyreneVersion::parse(a);
             let b = CyreneVersion::parse(b);
@@ -164,12 +633,116 @@ impl CyreneManager {
         Ok(required_version)
     }
 
+    /// Latest version in a channel of the app, or matching a version requirement
+    pub async fn resolve_version_spec(
+        &self,
+        name: &str,
+        spec: &str,
+    ) -> Result<Option<String>, CyreneError> {
+        let app_config = self.load_app(name)?;
+        match app_config.channels.get(spec) {
+            Some(pattern) => Ok(self
+                .versions(name)
+                .await?
+                .into_iter()
+                .find(|f| util::matches_pattern(pattern, f))),
+            None => self.get_latest_major_release(name, spec).await,
+        }
+    }
+
+    /// Resolve the version to install for `name@version`, or the latest version without one.
+    ///
+    /// `recommended` stands for the version the plugin recommends. Returns the version with the
+    /// requirement or channel it was resolved from, if it was not exact.
+    pub async fn resolve_install_version(
+        &self,
+        name: &str,
+        version: Option<&str>,
+    ) -> Result<(String, Option<String>), CyreneError> {
+        let app_config = self.load_app(name)?;
+        let Some(version) = version else {
+            return Ok((self.get_latest_version(name).await?, None));
+        };
+        let version = if version == "recommended" {
+            app_config
+                .recommended
+                .as_deref()
+                .ok_or(CyreneError::AppNoRecommended(name.to_string()))?
+        } else {
+            version
+        };
+        let version = if app_config.settings.semver {
+            util::normalize_version(version)
+        } else {
+            version
+        };
+        let is_exact = !app_config.channels.contains_key(version)
+            && (!app_config.settings.semver
+                || matches!(CyreneVersion::parse(version), CyreneVersion::Semver(_)));
+        if is_exact {
+            return Ok((version.to_string(), None));
+        }
+        match self.resolve_version_spec(name, version).await? {
+            Some(resolved) => Ok((resolved, Some(version.to_string()))),
+            None => Err(CyreneError::AppVersionNotFound(
+                name.to_string(),
+                version.to_string(),
+            )),
+        }
+    }
+
+    pub fn find_upgrade_prunable_versions(
+        &self,
+        name: &str,
+        old_version: &str,
+        new_version: &str,
+    ) -> Result<Vec<String>, CyreneError> {
+        let keep_on_upgrade = self.config.keep_on_upgrade();
+        if keep_on_upgrade == 0 {
+            return Ok(vec![old_version.to_string()]);
+        }
+        // Only prune previous versions of the release line being upgraded
+        let mut previous_versions = Vec::new();
+        for (_, version) in self.list_installed_app_versions(name)? {
+            if !version.eq(new_version) && util::is_major_version_equal(old_version, &version)? {
+                previous_versions.push(version);
+            }
+        }
+        debug!(
+            "Keeping {} previous versions of {}: {:?}",
+            keep_on_upgrade, name, previous_versions
+        );
+
+        Ok(previous_versions
+            .into_iter()
+            .skip(keep_on_upgrade)
+            .collect())
+    }
+
     pub fn is_version_installed(&self, name: &str, version: &str) -> Result<bool, CyreneError> {
         let installation_path = self.dirs.installation_path(name, version);
         fs::exists(&installation_path)
             .map_err(|e| CyreneError::AppCheck(name.to_string(), version.to_string(), e))
     }
 
+    /// Check that all binaries of an installed version exist
+    pub fn is_version_intact(&self, name: &str, version: &str) -> Result<bool, CyreneError> {
+        let app = self.load_app(name)?;
+        let installation_path = self.dirs.installation_path(name, version);
+        for bin_path in app.binaries(version)?.values() {
+            let mut canonical_path = installation_path.clone();
+            canonical_path.push(bin_path);
+            let exists = fs::exists(&canonical_path)
+                .map_err(|e| CyreneError::AppCheck(name.to_string(), version.to_string(), e))?;
+            if !exists {
+                debug!("Binary {} is missing", canonical_path.to_string_lossy());
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
     pub fn check_upgrade_latest(&self, name: &str) -> Result<bool, CyreneError> {
         let app = self.load_app(name)?;
         let upgrade_latest = app.upgrade_latest();
@@ -177,12 +750,242 @@ impl CyreneManager {
         Ok(upgrade_latest)
     }
 
+    pub fn find_binary_providers(
+        &self,
+        binary: &str,
+    ) -> Result<Vec<(String, String, PathBuf)>, CyreneError> {
+        let mut providers = Vec::new();
+        for (name, version) in self.get_app_version_map()? {
+            let app = match self.load_app(&name) {
+                Ok(app) => app,
+                Err(e) => {
+                    debug!("Skipping {}: {}", name, e);
+                    continue;
+                }
+            };
+            if let Some(bin_path) = app.binaries(&version)?.get(binary) {
+                let mut canonical_path = self.dirs.installation_path(&name, &version);
+                canonical_path.push(bin_path);
+                providers.push((name, version, canonical_path));
+            }
+        }
+
+        Ok(providers)
+    }
+
+    pub fn list_linked_binaries(&self) -> Result<Vec<CyreneLinkedBinary>, CyreneError> {
+        let mut exe_dirs = BTreeSet::from([self.dirs.exe_dir.clone()]);
+        exe_dirs.extend(
+            self.config
+                .bin_overrides
+                .iter()
+                .flatten()
+                .map(|f| f.1.clone()),
+        );
+        let mut entries = Vec::new();
+        for exe_dir in exe_dirs {
+            if exe_dir.ne(&self.dirs.exe_dir) && !exe_dir.is_dir() {
+                continue;
+            }
+            let list_dirs =
+                fs::read_dir(&exe_dir).map_err(|e| CyreneError::AppList(exe_dir.clone(), e))?;
+            entries.extend(list_dirs.filter_map(|p| p.ok()));
+        }
+        let mut candidates = None;
+        let mut linked = Vec::new();
+        for entry in entries {
+            let path = entry.path();
+            let (app, version, target) = match fs::read_link(&path) {
+                // Anything that is not a symlink into the apps directory is not ours
+                Ok(target) => match self.dirs.find_installation(&target) {
+                    Some((app, version)) => (app, version, target),
+                    None => continue,
+                },
+                // Hardlinks and copies are recognized by the binaries they would link
+                Err(_) => {
+                    let candidates = match &mut candidates {
+                        Some(candidates) => candidates,
+                        None => candidates.insert(self.link_candidates()?),
+                    };
+                    match candidates.remove(&path) {
+                        Some((app, version, target)) if self.is_linked_to(&path, &target) => {
+                            (app, version, target)
+                        }
+                        _ => continue,
+                    }
+                }
+            };
+            linked.push(CyreneLinkedBinary {
+                binary: entry.file_name().to_string_lossy().to_string(),
+                app,
+                version,
+                valid: target.exists(),
+                target,
+            });
+        }
+        linked.sort_by(|a, b| a.binary.cmp(&b.binary));
+
+        Ok(linked)
+    }
+
+    /// Binaries of every app in the lockfile at its linked version, with their link paths
+    pub fn list_lockfile_links(&self) -> Result<Vec<CyreneAppLink>, CyreneError> {
+        let mut links = Vec::new();
+        for (name, version) in self.get_app_version_map()? {
+            let app = match self.load_app(&name) {
+                Ok(app) => app,
+                Err(e) => {
+                    debug!("Skipping {}: {}", name, e);
+                    continue;
+                }
+            };
+            let installation_path = self.dirs.installation_path(&name, &version);
+            let exe_dir = self.app_exe_dir(&name);
+            for (binary, bin_path) in app.binaries(&version)? {
+                links.push(CyreneAppLink {
+                    path: exe_dir.join(&binary),
+                    target: installation_path.join(bin_path),
+                    binary,
+                    app: name.clone(),
+                    version: version.clone(),
+                });
+            }
+        }
+        links.sort_by(|a, b| a.binary.cmp(&b.binary));
+
+        Ok(links)
+    }
+
+    /// Find binaries provided by more than one installed app.
+    ///
+    /// Apps are checked at their linked version, or their newest installed version otherwise.
+    pub fn find_binary_conflicts(&self) -> Result<Vec<CyreneBinaryConflict>, CyreneError> {
+        let linked_versions = self.get_app_version_map()?;
+        let mut providers: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+        for name in self.list_apps()? {
+            let version = match linked_versions.get(&name) {
+                Some(version) => version.clone(),
+                None => match self.list_installed_app_versions(&name)?.into_iter().next() {
+                    Some((_, version)) => version,
+                    None => continue,
+                },
+            };
+            let app = match self.load_app(&name) {
+                Ok(app) => app,
+                Err(e) => {
+                    debug!("Skipping {}: {}", name, e);
+                    continue;
+                }
+            };
+            for binary in app.binaries(&version)?.into_keys() {
+                providers
+                    .entry(binary)
+                    .or_default()
+                    .push((name.clone(), version.clone()));
+            }
+        }
+
+        let linked: BTreeMap<String, String> = self
+            .list_linked_binaries()?
+            .into_iter()
+            .map(|f| (f.binary, f.app))
+            .collect();
+        Ok(providers
+            .into_iter()
+            .filter(|(_, providers)| providers.len() > 1)
+            .map(|(binary, providers)| CyreneBinaryConflict {
+                linked: linked.get(&binary).cloned(),
+                binary,
+                providers,
+            })
+            .collect())
+    }
+
+    /// Find problems with links, installs and the version cache
+    pub fn diagnose(&self) -> Result<Vec<CyreneIssue>, CyreneError> {
+        let mut issues = Vec::new();
+        // Check the cache first, since reading a corrupt cache replaces it
+        if let Some(error) = self.version_cache.check_cache()? {
+            issues.push(CyreneIssue::CorruptCache {
+                path: self.version_cache.cache_path().to_path_buf(),
+                error,
+            });
+        }
+        for linked in self.list_linked_binaries()? {
+            if !linked.valid {
+                issues.push(CyreneIssue::DanglingSymlink {
+                    binary: linked.binary,
+                    target: linked.target,
+                });
+            }
+        }
+        for name in self.list_apps()? {
+            if !self.app_path(&name).exists() {
+                issues.push(CyreneIssue::OrphanApp { app: name });
+            }
+        }
+        for (name, version) in self.get_app_version_map()? {
+            if !self.is_version_installed(&name, &version)? {
+                issues.push(CyreneIssue::MissingInstall { app: name, version });
+            }
+        }
+
+        Ok(issues)
+    }
+
+    pub async fn freeze_lockfile(&self) -> Result<BTreeMap<String, String>, CyreneError> {
+        let mut versions = BTreeMap::new();
+        for (name, version) in self.get_app_version_map()? {
+            let frozen_version = if self.is_version_installed(&name, &version)? {
+                version
+            } else if let Some(installed) = self.find_installed_major_release(&name, &version)? {
+                installed
+            } else {
+                self.get_latest_major_release(&name, &version)
+                    .await?
+                    .ok_or(CyreneError::AppVersionNotFound(name.clone(), version))?
+            };
+            versions.insert(name, frozen_version);
+        }
+
+        Ok(versions)
+    }
+
+    pub fn write_lockfile(
+        &self,
+        lockfile_path: Option<&Path>,
+        versions: BTreeMap<String, String>,
+    ) -> Result<(), CyreneError> {
+        match lockfile_path {
+            Some(lockfile_path) => self.lockfile.write_lockfile(lockfile_path, versions),
+            None => {
+                for (name, version) in versions {
+                    self.lockfile.update_lockfile(&name, Some(&version))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn check_lockfile(&self) -> Result<Vec<(String, String, bool)>, CyreneError> {
+        let lockfile_items = self.lockfile.load_version_map_from_current_lockfile()?;
+        let mut results = Vec::new();
+        for (name, version) in lockfile_items {
+            // Populate versions cache if needed
+            self.versions(&name).await?;
+            let available = self.verify_version_exists(&name, &version)?;
+            results.push((name, version, available));
+        }
+
+        Ok(results)
+    }
+
     pub async fn load_lockfile(
         &self,
-        loaded_lockfile: Option<&Path>,
+        loaded_lockfiles: Option<&[PathBuf]>,
     ) -> Result<Vec<TransactionCommands>, CyreneError> {
-        match &loaded_lockfile {
-            Some(loaded_lockfile) => self.lockfile.use_local_lockfile(loaded_lockfile)?,
+        match &loaded_lockfiles {
+            Some(loaded_lockfiles) => self.lockfile.use_local_lockfiles(loaded_lockfiles)?,
             None => self.lockfile.use_default_lockfile()?,
         };
         let lockfile_items = self.lockfile.load_version_map_from_current_lockfile()?;
@@ -206,6 +1009,11 @@ impl CyreneManager {
                     app: lockfile_item.0.clone(),
                     version: lockfile_item.1.clone(),
                 });
+            } else if !self.is_version_intact(&lockfile_item.0, &lockfile_item.1)? {
+                transactions.push(TransactionCommands::Reinstall {
+                    app: lockfile_item.0.clone(),
+                    version: lockfile_item.1.clone(),
+                });
             }
             transactions.push(TransactionCommands::Link {
                 app: lockfile_item.0.clone(),
@@ -217,21 +1025,73 @@ impl CyreneManager {
         Ok(transactions)
     }
     // Transactions
-    pub async fn install_version(&self, name: &str, version: &str) -> Result<(), CyreneError> {
+    pub async fn install_version(
+        &self,
+        name: &str,
+        version: &str,
+        cancel: &Cancellation,
+    ) -> Result<(), CyreneError> {
+        let _guard = self.install_locks.lock(name, version).await;
         let installation_path = self.dirs.ensure_installation_dir(name, version)?;
         let app = self.load_app(name)?;
-        app.install(version, &installation_path).await?;
+        self.with_plugin_timeout(
+            name,
+            app.install(version, &installation_path, &self.config, cancel),
+        )
+        .await?;
+
+        Ok(())
+    }
+    // Transactions
+    pub async fn install_version_from_local(
+        &self,
+        name: &str,
+        version: &str,
+        local_path: &Path,
+        cancel: &Cancellation,
+    ) -> Result<(), CyreneError> {
+        let _guard = self.install_locks.lock(name, version).await;
+        let installation_path = self.dirs.ensure_installation_dir(name, version)?;
+        sources::from_local(local_path, &installation_path, cancel).await?;
 
         Ok(())
     }
     // Transactions
     pub async fn post_install_version(&self, name: &str, version: &str) -> Result<(), CyreneError> {
+        let _guard = self.install_locks.lock(name, version).await;
         let installation_path = self.dirs.installation_path(name, version);
         let app = self.load_app(name)?;
-        app.post_install(version, &installation_path).await?;
+        let result = match app
+            .post_install(version, &installation_path, &self.config)
+            .await
+        {
+            Ok(()) => app.verify(version, &installation_path),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            // Do not leave a broken installation behind
+            fs::remove_dir_all(&installation_path)
+                .map_err(|e| CyreneError::AppRemove(name.to_string(), version.to_string(), e))?;
+            return Err(e);
+        }
 
         Ok(())
     }
+    /// Version requirement recorded when the app was installed
+    pub fn find_version_range(&self, name: &str) -> Result<Option<String>, CyreneError> {
+        Ok(self
+            .get_lockfile_entries()?
+            .get(name)
+            .and_then(|f| f.range())
+            .map(String::from))
+    }
+    pub fn update_lockfile_range(
+        &self,
+        name: &str,
+        range: Option<&str>,
+    ) -> Result<(), CyreneError> {
+        self.lockfile.update_lockfile_range(name, range)
+    }
     pub fn update_lockfile(&self, name: &str, version: Option<&str>) -> Result<(), CyreneError> {
         debug!(
             "Updating lockfile: app version {:?} for plugin {}",
@@ -244,6 +1104,80 @@ impl CyreneManager {
         name: &str,
         version: &str,
         overwrite: bool,
+    ) -> Result<bool, CyreneError> {
+        let exe_dir = self.app_exe_dir(name);
+        fs::create_dir_all(&exe_dir).map_err(|e| CyreneError::DirectoryInit(exe_dir.clone(), e))?;
+        let not_overwritten_exists =
+            self.link_binaries_in(name, version, &exe_dir, self.config.shims(), overwrite)?;
+        if let Some(suffix) = self.version_suffix(version) {
+            self.link_suffixed_binaries(name, version, &suffix, overwrite)?;
+        }
+
+        Ok(not_overwritten_exists)
+    }
+
+    /// Link the binaries of an installed app version into a temporary directory and report them.
+    ///
+    /// The directory is removed afterwards, so `exe_dir` is left untouched.
+    pub fn test_link(&self, name: &str, version: &str) -> Result<Vec<CyreneTestLink>, CyreneError> {
+        let test_dir =
+            tempfile::tempdir().map_err(|e| CyreneError::DirectoryInit(std::env::temp_dir(), e))?;
+        self.link_binaries_in(name, version, test_dir.path(), false, false)?;
+
+        let app = self.load_app(name)?;
+        let installation_path = self.dirs.installation_path(name, version);
+        Ok(app
+            .binaries(version)?
+            .into_iter()
+            .map(|(bin_name, bin_path)| {
+                let exe_path = test_dir.path().join(&bin_name);
+                CyreneTestLink {
+                    created: fs::symlink_metadata(&exe_path).is_ok(),
+                    target_exists: exe_path.exists(),
+                    target: installation_path.join(bin_path),
+                    binary: bin_name,
+                }
+            })
+            .collect())
+    }
+
+    /// Steps installing and linking a version would take, without running them
+    pub fn explain(&self, name: &str, version: &str) -> Result<Vec<AppPlanStep>, CyreneError> {
+        let app = self.load_app(name)?;
+        let installation_path = self.dirs.installation_path(name, version);
+        let mut steps = app.plan(version);
+        steps.insert(
+            1,
+            AppPlanStep::new(
+                "Create directory",
+                installation_path.to_string_lossy().to_string(),
+                false,
+            ),
+        );
+        let mut binaries: Vec<(String, String)> = app.binaries(version)?.into_iter().collect();
+        binaries.sort();
+        for (bin_name, bin_path) in binaries {
+            steps.push(AppPlanStep::new(
+                "Link",
+                format!(
+                    "{} to {}",
+                    self.app_exe_dir(name).join(bin_name).to_string_lossy(),
+                    installation_path.join(bin_path).to_string_lossy()
+                ),
+                false,
+            ));
+        }
+
+        Ok(steps)
+    }
+
+    fn link_binaries_in(
+        &self,
+        name: &str,
+        version: &str,
+        exe_dir: &Path,
+        shims: bool,
+        overwrite: bool,
     ) -> Result<bool, CyreneError> {
         let app = self.load_app(name)?;
         debug!("Using app version {} for plugin {}", version, &name);
@@ -263,11 +1197,16 @@ impl CyreneManager {
 
         let binaries = app.binaries(version)?;
         let mut not_overwritten_exists = false;
+        let versioned_exe_dir = self.versioned_exe_dir(name, version);
+        if shims {
+            fs::create_dir_all(&versioned_exe_dir)
+                .map_err(|e| CyreneError::DirectoryInit(versioned_exe_dir.clone(), e))?;
+        }
 
         for (bin_name, bin_path) in binaries {
             let mut canonical_path = installation_path.clone();
             canonical_path.push(&bin_path);
-            let mut exe_path = self.dirs.exe_dir.clone();
+            let mut exe_path = exe_dir.to_path_buf();
             exe_path.push(&bin_name);
             debug!(
                 "Attempting to link {} to {}",
@@ -282,6 +1221,23 @@ impl CyreneManager {
                 return Err(CyreneError::AppLinkingToSelf);
             }
 
+            if shims {
+                let mut versioned_path = versioned_exe_dir.clone();
+                versioned_path.push(&bin_name);
+                if fs::symlink_metadata(&versioned_path).is_ok() {
+                    fs::remove_file(&versioned_path).map_err(|e| {
+                        CyreneError::AppLinkRemove(versioned_path.to_string_lossy().to_string(), e)
+                    })?;
+                }
+                symlink::symlink_file(&canonical_path, &versioned_path).map_err(|e| {
+                    CyreneError::AppLinkCreate(
+                        versioned_path.to_string_lossy().to_string(),
+                        canonical_path.to_string_lossy().to_string(),
+                        e,
+                    )
+                })?;
+            }
+
             if let Ok(metadata) = fs::metadata(&exe_path) {
                 let symlink_path = if metadata.is_symlink() {
                     fs::read_link(&exe_path).map_err(|e| {
@@ -300,13 +1256,7 @@ impl CyreneManager {
                     fs::remove_file(&exe_path).map_err(|e| {
                         CyreneError::AppLinkRemove(exe_path.to_string_lossy().to_string(), e)
                     })?;
-                    symlink::symlink_file(&canonical_path, &exe_path).map_err(|e| {
-                        CyreneError::AppLinkCreate(
-                            exe_path.to_string_lossy().to_string(),
-                            canonical_path.to_string_lossy().to_string(),
-                            e,
-                        )
-                    })?;
+                    self.create_link(name, &bin_name, &canonical_path, &exe_path, shims)?;
                 } else {
                     not_overwritten_exists = true;
                     debug!(
@@ -321,24 +1271,49 @@ impl CyreneManager {
                     exe_path.to_string_lossy(),
                     canonical_path.to_string_lossy()
                 );
-                if overwrite {
+                if overwrite && fs::symlink_metadata(&exe_path).is_ok() {
                     fs::remove_file(&exe_path).map_err(|e| {
                         CyreneError::AppLinkRemove(exe_path.to_string_lossy().to_string(), e)
                     })?;
                 }
-                symlink::symlink_file(&canonical_path, &exe_path).map_err(|e| {
-                    CyreneError::AppLinkCreate(
-                        exe_path.to_string_lossy().to_string(),
-                        canonical_path.to_string_lossy().to_string(),
-                        e,
-                    )
-                })?;
+                self.create_link(name, &bin_name, &canonical_path, &exe_path, shims)?;
             }
         }
 
         Ok(not_overwritten_exists)
     }
 
+    pub fn is_version_linked(&self, name: &str, version: &str) -> Result<bool, CyreneError> {
+        let app = self.load_app(name)?;
+        let installation_path = self.dirs.installation_path(name, version);
+
+        for (bin_name, bin_path) in app.binaries(version)? {
+            let mut canonical_path = installation_path.clone();
+            canonical_path.push(&bin_path);
+            let mut exe_path = self.app_exe_dir(name);
+            exe_path.push(&bin_name);
+            if self.config.shims() {
+                // The shim itself does not point to a version
+                if !exe_path.exists() {
+                    return Ok(false);
+                }
+                exe_path = self.versioned_exe_dir(name, version);
+                exe_path.push(&bin_name);
+            }
+
+            if !self.is_linked_to(&exe_path, &canonical_path) {
+                debug!(
+                    "{} is not linked to {}",
+                    exe_path.to_string_lossy(),
+                    canonical_path.to_string_lossy()
+                );
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
     pub fn unlink_binaries(&self, name: &str) -> Result<(), CyreneError> {
         let app = self.load_app(name)?;
         debug!("Unlinking app versions for plugin {}", &name);
@@ -346,7 +1321,7 @@ impl CyreneManager {
         let binaries = app.binaries("")?;
 
         for (bin_name, _) in binaries {
-            let mut exe_path = self.dirs.exe_dir.clone();
+            let mut exe_path = self.app_exe_dir(name);
             exe_path.push(&bin_name);
 
             if fs::exists(&exe_path)
@@ -358,8 +1333,96 @@ impl CyreneManager {
                 })?;
             }
         }
+        // Suffixed links of other versions are kept, so they stay on PATH
+        if let Some(version) = self.find_installed_version(name)? {
+            self.unlink_suffixed_binaries(name, &version)?;
+        }
+        for (_, version) in self.list_installed_app_versions(name)? {
+            let versioned_exe_dir = self.versioned_exe_dir(name, &version);
+            if versioned_exe_dir.is_dir() {
+                debug!("unlinking {}", versioned_exe_dir.to_string_lossy());
+                fs::remove_dir_all(&versioned_exe_dir).map_err(|e| {
+                    CyreneError::AppLinkRemove(versioned_exe_dir.to_string_lossy().to_string(), e)
+                })?;
+            }
+        }
         Ok(())
     }
+
+    /// Installed version selected by the nearest `cyrene.lock`, falling back to the current
+    /// lockfile
+    fn resolve_version(&self, name: &str) -> Result<String, CyreneError> {
+        let current_dir = std::env::current_dir().map_err(CyreneError::CurrentDir)?;
+        let version = match self
+            .lockfile
+            .find_version_in_nearest_lockfile(&current_dir, name)?
+        {
+            Some(version) => version,
+            None => self
+                .find_installed_version(name)?
+                .ok_or(CyreneError::AppNotInstalled(
+                    name.to_string(),
+                    "".to_string(),
+                ))?,
+        };
+        if !self.is_version_installed(name, &version)? {
+            return Err(CyreneError::AppNotInstalled(name.to_string(), version));
+        }
+
+        Ok(version)
+    }
+
+    /// Path of a binary in the version selected by the nearest `cyrene.lock`, falling back to the
+    /// current lockfile. Without a binary name, the binary named after the app or the app's only
+    /// binary is used
+    pub fn resolve_binary(&self, name: &str, binary: Option<&str>) -> Result<PathBuf, CyreneError> {
+        let version = self.resolve_version(name)?;
+        let app = self.load_app(name)?;
+        let mut binaries = app.binaries(&version)?;
+        let bin_path = match binary {
+            Some(binary) => binaries
+                .remove(binary)
+                .ok_or(CyreneError::AppBinaryNotFound(
+                    name.to_string(),
+                    binary.to_string(),
+                ))?,
+            None if binaries.contains_key(name) => binaries.remove(name).unwrap(),
+            None if binaries.len() == 1 => binaries.into_values().next().unwrap(),
+            None => {
+                let mut names: Vec<String> = binaries.into_keys().collect();
+                names.sort();
+                return Err(CyreneError::AppBinaryAmbiguous(
+                    name.to_string(),
+                    names.join(", "),
+                ));
+            }
+        };
+        let mut binary_path = self.dirs.installation_path(name, &version);
+        binary_path.push(bin_path);
+
+        Ok(binary_path)
+    }
+
+    /// Command for an action of an app, in the version selected like [`Self::resolve_binary`]
+    pub fn action_command(
+        &self,
+        name: &str,
+        action: &str,
+        args: &[String],
+    ) -> Result<process::Command, CyreneError> {
+        let version = self.resolve_version(name)?;
+        let app = self.load_app(name)?;
+        let installation_path = self.dirs.installation_path(name, &version);
+        let (bin_path, action_args) = app.action(action, &version)?;
+
+        let mut command = process::Command::new(installation_path.join(bin_path));
+        command
+            .args(action_args)
+            .args(args)
+            .env("CYRENE_APP_VERSION", &version)
+            .env("CYRENE_APP_DIR", &installation_path);
+        Ok(command)
+    }
     pub fn uninstall_version(&self, name: &str, version: &str) -> Result<(), CyreneError> {
         debug!("Uninstalling app version {} for plugin {}", version, name);
 
@@ -370,6 +1433,7 @@ impl CyreneManager {
                 version.to_string(),
             ));
         }
+        self.unlink_suffixed_binaries(name, version)?;
         let installation_path = self.dirs.installation_path(name, version);
 
         fs::remove_dir_all(&installation_path)
@@ -380,18 +1444,539 @@ impl CyreneManager {
 
     pub fn uninstall_all(&self, name: &str) -> Result<(), CyreneError> {
         debug!("Uninstalling app versions for plugin {}", name);
-        let installation_path = self.dirs.installation_root(name);
-        if !fs::exists(&installation_path)
-            .map_err(|e| CyreneError::AppCheck(name.to_string(), "".to_string(), e))?
-        {
+        let versions = self
+            .dirs
+            .installed_versions(name)
+            .map_err(|e| CyreneError::AppCheck(name.to_string(), "".to_string(), e))?;
+        if versions.is_empty() {
             return Err(CyreneError::AppNotInstalled(
                 name.to_string(),
                 "".to_string(),
             ));
         }
-        fs::remove_dir_all(&installation_path)
-            .map_err(|e| CyreneError::AppRemove(name.to_string(), "".to_string(), e))?;
+        for version in versions {
+            self.unlink_suffixed_binaries(name, &version)?;
+            let installation_path = self.dirs.installation_path(name, &version);
+            fs::remove_dir_all(&installation_path)
+                .map_err(|e| CyreneError::AppRemove(name.to_string(), version.to_string(), e))?;
+        }
+        if let Some(installation_root) = self.dirs.installation_root(name) {
+            fs::remove_dir_all(&installation_root)
+                .map_err(|e| CyreneError::AppRemove(name.to_string(), "".to_string(), e))?;
+        }
 
         Ok(())
     }
+
+    /// Move installed versions into the configured layout
+    pub fn migrate_layout(&self) -> Result<Vec<(String, String)>, CyreneError> {
+        self.dirs
+            .migrate_layout()
+            .map_err(CyreneError::LayoutMigrate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_manager(root: &Path, config: CyreneConfig) -> CyreneManager {
+        let dirs = CyreneDirs::in_root(root);
+        dirs.init_dirs().unwrap();
+        let cache_manager = Box::new(CyreneVersionCacheManager::new(&dirs.version_cache_path));
+        let lockfile_manager = Box::new(CyreneLockfileManager::new(&dirs.lockfile_path()));
+        let registry_manager = Box::new(CyreneRegistryManager::new(
+            None,
+            &dirs.registry_index_path,
+            reqwest::Client::new(),
+        ));
+
+        CyreneManager::new(
+            Arc::new(config),
+            Arc::new(dirs),
+            lockfile_manager,
+            cache_manager,
+            registry_manager,
+        )
+    }
+
+    /// Write a plugin with `binaries` and install `versions` of it
+    fn add_app(manager: &CyreneManager, name: &str, binaries: &[&str], versions: &[&str]) {
+        add_app_with(manager, name, binaries, versions, "");
+    }
+
+    /// Like [`add_app`], with `extra` at the top of the recipe
+    fn add_app_with(
+        manager: &CyreneManager,
+        name: &str,
+        binaries: &[&str],
+        versions: &[&str],
+        extra: &str,
+    ) {
+        let binaries: String = binaries
+            .iter()
+            .map(|f| format!("{} = \"bin/{}\"\n", f, f))
+            .collect();
+        let recipe = format!(
+            r#"{extra}
+[settings]
+upgrade_latest = false
+
+[versions]
+type = "github"
+repo = "Damillora/{name}"
+
+[[sources]]
+type = "file"
+url = "https://example.com/{name}"
+
+[binaries]
+{binaries}"#
+        );
+        fs::write(manager.get_app_path(name), recipe).unwrap();
+        for version in versions {
+            let bin_dir = manager
+                .dirs
+                .ensure_installation_dir(name, version)
+                .unwrap()
+                .join("bin");
+            fs::create_dir_all(&bin_dir).unwrap();
+            for binary in manager.load_app(name).unwrap().binaries.keys() {
+                fs::write(bin_dir.join(binary), version).unwrap();
+            }
+        }
+    }
+
+    /// Serve `body` to a single request, returning the port and the server thread
+    fn serve_once(body: Vec<u8>) -> (u16, std::thread::JoinHandle<()>) {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        });
+
+        (port, server)
+    }
+
+    #[tokio::test]
+    async fn post_install_removes_install_with_bad_signature() {
+        use crate::app_module::sources::tests::{sign, signing_key};
+        use pgp::ser::Serialize;
+
+        let (secret_key, public_key) = signing_key();
+        let signature = sign(&secret_key, b"tampered").to_bytes().unwrap();
+        let (port, server) = serve_once(signature);
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(root.path(), CyreneConfig::default());
+        let post_install = format!(
+            r#"
+[[post_install]]
+type = "verify_gpg"
+path = "bin/node"
+signature_url = "http://127.0.0.1:{port}/node.sig"
+public_key = """
+{public_key}"""
+"#
+        );
+        add_app_with(&manager, "node", &["node"], &["20.0.0"], &post_install);
+
+        assert!(matches!(
+            manager.post_install_version("node", "20.0.0").await,
+            Err(CyreneError::SignatureVerify(..))
+        ));
+        server.join().unwrap();
+        assert!(!manager.dirs.installation_path("node", "20.0.0").exists());
+    }
+
+    #[tokio::test]
+    async fn post_install_removes_install_with_bad_checksum() {
+        let (port, server) = serve_once(b"0000  node\n".to_vec());
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(root.path(), CyreneConfig::default());
+        let post_install = format!(
+            r#"
+[[post_install]]
+type = "verify_sha256"
+path = "bin/node"
+checksums_url = "http://127.0.0.1:{port}/SHASUMS256.txt"
+"#
+        );
+        add_app_with(&manager, "node", &["node"], &["20.0.0"], &post_install);
+
+        assert!(matches!(
+            manager.post_install_version("node", "20.0.0").await,
+            Err(CyreneError::ChecksumMismatch(..))
+        ));
+        server.join().unwrap();
+        assert!(!manager.dirs.installation_path("node", "20.0.0").exists());
+    }
+
+    #[tokio::test]
+    async fn install_from_local_waits_for_install_lock() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(root.path(), CyreneConfig::default());
+        let local_path = root.path().join("node");
+        fs::write(&local_path, "20.0.0").unwrap();
+
+        let guard = manager.install_locks.lock("node", "20.0.0").await;
+        let cancel = Cancellation::default();
+        let installs = async {
+            tokio::join!(
+                manager.install_version_from_local("node", "20.0.0", &local_path, &cancel),
+                manager.install_version_from_local("node", "20.0.0", &local_path, &cancel),
+            )
+        };
+        tokio::pin!(installs);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), &mut installs)
+                .await
+                .is_err()
+        );
+        let installation_path = manager.dirs.installation_path("node", "20.0.0");
+        assert!(!installation_path.join("node").exists());
+
+        drop(guard);
+        let (first, second) = installs.await;
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(
+            fs::read_to_string(installation_path.join("node")).unwrap(),
+            "20.0.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn install_timeout_removes_install() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(
+            root.path(),
+            CyreneConfig {
+                install_timeout: Some(0),
+                ..Default::default()
+            },
+        );
+        let installation_path = manager
+            .dirs
+            .ensure_installation_dir("node", "20.0.0")
+            .unwrap();
+
+        let cancel = Cancellation::default();
+        let install = async {
+            fs::write(installation_path.join("node"), "20.0.0").unwrap();
+            std::future::pending::<Result<(), CyreneError>>().await
+        };
+        assert!(matches!(
+            manager
+                .with_install_timeout("node", "20.0.0", &cancel, install)
+                .await,
+            Err(CyreneError::InstallTimeout(..))
+        ));
+        assert!(!installation_path.exists());
+    }
+
+    fn link_with_mode(root: &Path, link_mode: LinkMode) -> CyreneManager {
+        let manager = test_manager(
+            root,
+            CyreneConfig {
+                link_mode: Some(link_mode),
+                ..Default::default()
+            },
+        );
+        add_app(&manager, "node", &["node"], &["20.0.0"]);
+        add_app(&manager, "nodejs", &["node"], &["20.0.0"]);
+        manager.link_binaries("node", "20.0.0", true).unwrap();
+        manager.update_lockfile("node", Some("20.0.0")).unwrap();
+
+        manager
+    }
+
+    #[test]
+    fn link_hardlink_is_recognized() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = link_with_mode(root.path(), LinkMode::Hardlink);
+
+        let exe_path = manager.dirs.exe_dir.join("node");
+        let canonical_path = manager
+            .dirs
+            .installation_path("node", "20.0.0")
+            .join("bin/node");
+        let (exe, canonical) = (
+            fs::symlink_metadata(&exe_path).unwrap(),
+            fs::metadata(&canonical_path).unwrap(),
+        );
+        assert!(!exe.is_symlink());
+        assert_eq!(exe.ino(), canonical.ino());
+
+        let linked = manager.list_linked_binaries().unwrap();
+        assert_eq!(linked.len(), 1);
+        assert_eq!(linked[0].app, "node");
+        assert!(linked[0].valid);
+        let conflicts = manager.find_binary_conflicts().unwrap();
+        assert_eq!(conflicts[0].linked.as_deref(), Some("node"));
+        assert!(manager.diagnose().unwrap().is_empty());
+    }
+
+    #[test]
+    fn link_copy_is_recognized() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = link_with_mode(root.path(), LinkMode::Copy);
+
+        let exe_path = manager.dirs.exe_dir.join("node");
+        let canonical_path = manager
+            .dirs
+            .installation_path("node", "20.0.0")
+            .join("bin/node");
+        let (exe, canonical) = (
+            fs::symlink_metadata(&exe_path).unwrap(),
+            fs::metadata(&canonical_path).unwrap(),
+        );
+        assert!(!exe.is_symlink());
+        assert_ne!(exe.ino(), canonical.ino());
+        assert_eq!(fs::read_to_string(&exe_path).unwrap(), "20.0.0");
+
+        let linked = manager.list_linked_binaries().unwrap();
+        assert_eq!(linked.len(), 1);
+        assert_eq!(linked[0].app, "node");
+        let conflicts = manager.find_binary_conflicts().unwrap();
+        assert_eq!(conflicts[0].linked.as_deref(), Some("node"));
+    }
+
+    #[test]
+    fn link_hardlink_falls_back_across_devices() {
+        let dir = tempfile::tempdir().unwrap();
+        let canonical_path = dir.path().join("node");
+        let exe_path = dir.path().join("node-link");
+        fs::write(&canonical_path, "20.0.0").unwrap();
+
+        link_file_with_fallback(
+            LinkMode::Hardlink,
+            LinkMode::Copy,
+            &canonical_path,
+            &exe_path,
+            |link_mode, canonical_path, exe_path| match link_mode {
+                LinkMode::Hardlink => Err(io::ErrorKind::CrossesDevices.into()),
+                _ => link_file(link_mode, canonical_path, exe_path),
+            },
+        )
+        .unwrap();
+
+        let exe = fs::symlink_metadata(&exe_path).unwrap();
+        assert!(!exe.is_symlink());
+        assert_ne!(exe.ino(), fs::metadata(&canonical_path).unwrap().ino());
+        assert_eq!(fs::read_to_string(&exe_path).unwrap(), "20.0.0");
+    }
+
+    #[test]
+    fn link_writes_shim() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(
+            root.path(),
+            CyreneConfig {
+                shims: Some(true),
+                ..Default::default()
+            },
+        );
+        add_app(&manager, "node", &["node"], &["20.0.0"]);
+        manager.link_binaries("node", "20.0.0", true).unwrap();
+
+        let shim_path = manager.dirs.exe_dir.join("node");
+        let current_exe = std::env::current_exe().unwrap();
+        assert_eq!(
+            fs::read_to_string(&shim_path).unwrap(),
+            format!(
+                "#!/bin/sh\nexec \"{}\" exec \"node\" \"node\" -- \"$@\"\n",
+                current_exe.display()
+            )
+        );
+        let mode = fs::metadata(&shim_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[tokio::test]
+    async fn add_plugin_rejects_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(root.path(), CyreneConfig::default());
+        for name in ["", ".", "..", "../node", "node/", "nodejs/node", "/node"] {
+            assert!(
+                matches!(
+                    manager.add_plugin(name, false, false).await,
+                    Err(CyreneError::PluginNameInvalid(invalid)) if invalid == name
+                ),
+                "{name} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn link_uses_bin_overrides() {
+        let root = tempfile::tempdir().unwrap();
+        let override_dir = root.path().join("override");
+        let manager = test_manager(
+            root.path(),
+            CyreneConfig {
+                bin_overrides: Some(BTreeMap::from([("node".to_string(), override_dir.clone())])),
+                version_suffix: Some(VersionSuffix::Major),
+                ..Default::default()
+            },
+        );
+        add_app(&manager, "node", &["node"], &["20.0.0"]);
+        add_app(&manager, "go", &["go"], &["1.24.0"]);
+        manager.link_binaries("node", "20.0.0", true).unwrap();
+        manager.link_binaries("go", "1.24.0", true).unwrap();
+        manager.update_lockfile("node", Some("20.0.0")).unwrap();
+
+        let exe_dir = &manager.dirs.exe_dir;
+        assert!(override_dir.join("node").is_file());
+        assert!(override_dir.join("node-20").is_file());
+        assert!(fs::symlink_metadata(exe_dir.join("node")).is_err());
+        assert!(fs::symlink_metadata(exe_dir.join("node-20")).is_err());
+        assert!(exe_dir.join("go").is_file());
+        assert!(manager.is_version_linked("node", "20.0.0").unwrap());
+        let linked: Vec<_> = manager
+            .list_linked_binaries()
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.binary, f.app))
+            .collect();
+        assert!(linked.contains(&("node".to_string(), "node".to_string())));
+
+        manager.unlink_binaries("node").unwrap();
+        assert!(fs::symlink_metadata(override_dir.join("node")).is_err());
+        assert!(fs::symlink_metadata(override_dir.join("node-20")).is_err());
+        assert!(exe_dir.join("go").is_file());
+    }
+
+    #[tokio::test]
+    async fn resolve_install_version_recommended() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(root.path(), CyreneConfig::default());
+        add_app_with(
+            &manager,
+            "node",
+            &["node"],
+            &[],
+            "recommended = \"v20.1.0\"\n",
+        );
+        add_app_with(&manager, "deno", &["deno"], &[], "recommended = \"^2\"\n");
+        add_app(&manager, "go", &["go"], &[]);
+        manager
+            .version_cache
+            .update_version_cache(
+                "deno",
+                vec![
+                    "3.0.0".to_string(),
+                    "2.1.0".to_string(),
+                    "2.0.0".to_string(),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!(
+            manager
+                .resolve_install_version("node", Some("recommended"))
+                .await
+                .unwrap(),
+            ("20.1.0".to_string(), None)
+        );
+        assert_eq!(
+            manager
+                .resolve_install_version("deno", Some("recommended"))
+                .await
+                .unwrap(),
+            ("2.1.0".to_string(), Some("^2".to_string()))
+        );
+        assert!(matches!(
+            manager
+                .resolve_install_version("go", Some("recommended"))
+                .await,
+            Err(CyreneError::AppNoRecommended(name)) if name == "go"
+        ));
+    }
+
+    #[test]
+    fn unlink_keeps_suffixed_links_of_other_versions() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(
+            root.path(),
+            CyreneConfig {
+                version_suffix: Some(VersionSuffix::Major),
+                ..Default::default()
+            },
+        );
+        add_app(&manager, "node", &["node"], &["18.0.0", "20.0.0"]);
+        manager.link_binaries("node", "18.0.0", true).unwrap();
+        manager.link_binaries("node", "20.0.0", true).unwrap();
+        manager.update_lockfile("node", Some("20.0.0")).unwrap();
+
+        manager.unlink_binaries("node").unwrap();
+
+        let exe_dir = &manager.dirs.exe_dir;
+        assert!(fs::symlink_metadata(exe_dir.join("node")).is_err());
+        assert!(fs::symlink_metadata(exe_dir.join("node-20")).is_err());
+        assert!(fs::symlink_metadata(exe_dir.join("node-18")).is_ok());
+    }
+
+    #[test]
+    fn resolve_binary_named_after_app() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(root.path(), CyreneConfig::default());
+        add_app(&manager, "node", &["node", "npm"], &["20.0.0"]);
+        manager.update_lockfile("node", Some("20.0.0")).unwrap();
+
+        assert_eq!(
+            manager.resolve_binary("node", None).unwrap(),
+            manager
+                .dirs
+                .installation_path("node", "20.0.0")
+                .join("bin/node")
+        );
+    }
+
+    #[test]
+    fn resolve_binary_only_binary() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(root.path(), CyreneConfig::default());
+        add_app(&manager, "ripgrep", &["rg"], &["14.0.0"]);
+        manager.update_lockfile("ripgrep", Some("14.0.0")).unwrap();
+
+        assert_eq!(
+            manager.resolve_binary("ripgrep", None).unwrap(),
+            manager
+                .dirs
+                .installation_path("ripgrep", "14.0.0")
+                .join("bin/rg")
+        );
+    }
+
+    #[test]
+    fn resolve_binary_ambiguous() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = test_manager(root.path(), CyreneConfig::default());
+        add_app(&manager, "go", &["gofmt", "godoc"], &["1.22.0"]);
+        manager.update_lockfile("go", Some("1.22.0")).unwrap();
+
+        match manager.resolve_binary("go", None) {
+            Err(CyreneError::AppBinaryAmbiguous(name, binaries)) => {
+                assert_eq!(name, "go");
+                assert_eq!(binaries, "godoc, gofmt");
+            }
+            _ => panic!("expected an ambiguous binary error"),
+        }
+    }
 }
@@ -0,0 +1,103 @@
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+static QUIET: OnceLock<bool> = OnceLock::new();
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable output
+    #[default]
+    Text,
+    /// One JSON event per line on stdout, human-readable output on stderr
+    Jsonl,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CyreneEvent<'a> {
+    InstallStarted {
+        app: &'a str,
+        version: &'a str,
+    },
+    PostInstallStarted {
+        app: &'a str,
+        version: &'a str,
+    },
+    DownloadStarted {
+        url: &'a str,
+        len: Option<u64>,
+    },
+    DownloadFinished {
+        url: &'a str,
+    },
+    Removed {
+        app: &'a str,
+        version: Option<&'a str>,
+    },
+    LockfileUpdated {
+        app: &'a str,
+        version: Option<&'a str>,
+    },
+    Linked {
+        app: &'a str,
+        version: &'a str,
+    },
+    Unlinked {
+        app: &'a str,
+    },
+    Error {
+        message: String,
+    },
+}
+
+pub fn init(format: OutputFormat, quiet: bool) {
+    let _ = OUTPUT_FORMAT.set(format);
+    let _ = QUIET.set(quiet);
+}
+
+/// Whether informational output is suppressed
+pub fn is_quiet() -> bool {
+    QUIET.get().copied().unwrap_or_default()
+}
+
+pub fn is_jsonl() -> bool {
+    OUTPUT_FORMAT.get().copied().unwrap_or_default() == OutputFormat::Jsonl
+}
+
+/// Emit an event in JSON Lines mode
+pub fn emit(event: CyreneEvent) {
+    if is_jsonl()
+        && let Ok(line) = serde_json::to_string(&event)
+    {
+        println!("{}", line);
+    }
+}
+
+/// Print human-readable output, moved to stderr in JSON Lines mode and dropped in quiet mode
+macro_rules! outputln {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            if $crate::output::is_jsonl() {
+                eprintln!($($arg)*);
+            } else {
+                println!($($arg)*);
+            }
+        }
+    };
+}
+pub(crate) use outputln;
+
+/// Print the output a command was run for, like [`outputln`] but kept in quiet mode
+macro_rules! resultln {
+    ($($arg:tt)*) => {
+        if $crate::output::is_jsonl() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use resultln;
@@ -0,0 +1,137 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CyreneError;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct CyreneRegistryIndex {
+    #[serde(default)]
+    pub plugins: BTreeMap<String, CyreneRegistryEntry>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CyreneRegistryEntry {
+    /// URL of the plugin recipe
+    pub url: String,
+    pub description: Option<String>,
+}
+
+impl CyreneRegistryIndex {
+    /// Plugins whose name or description contains `query`
+    pub fn search(&self, query: &str) -> Vec<(String, CyreneRegistryEntry)> {
+        let query = query.to_lowercase();
+        self.plugins
+            .iter()
+            .filter(|(name, entry)| {
+                name.to_lowercase().contains(&query)
+                    || entry
+                        .description
+                        .as_ref()
+                        .is_some_and(|f| f.to_lowercase().contains(&query))
+            })
+            .map(|(name, entry)| (name.clone(), entry.clone()))
+            .collect()
+    }
+}
+
+pub struct CyreneRegistryManager {
+    registry_url: Option<String>,
+    index_path: PathBuf,
+    client: reqwest::Client,
+}
+// Private functions
+impl CyreneRegistryManager {
+    async fn fetch(&self, url: &str) -> Result<String, CyreneError> {
+        debug!("Calling {}", url);
+        self.client
+            .get(url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| CyreneError::RegistryFetch(url.to_string(), e))?
+            .text()
+            .await
+            .map_err(|e| CyreneError::RegistryFetch(url.to_string(), e))
+    }
+}
+impl CyreneRegistryManager {
+    pub fn new(registry_url: Option<&str>, index_path: &Path, client: reqwest::Client) -> Self {
+        Self {
+            registry_url: registry_url.map(|f| f.to_string()),
+            index_path: index_path.to_path_buf(),
+            client,
+        }
+    }
+
+    /// Fetch the index from the registry and cache it
+    pub async fn update_index(&self) -> Result<CyreneRegistryIndex, CyreneError> {
+        let registry_url = self
+            .registry_url
+            .as_ref()
+            .ok_or(CyreneError::RegistryNotConfigured)?;
+        let index_read = self.fetch(registry_url).await?;
+        let index: CyreneRegistryIndex =
+            toml::de::from_str(&index_read).map_err(CyreneError::RegistryDeserialize)?;
+        fs::write(&self.index_path, index_read).map_err(CyreneError::RegistryWrite)?;
+
+        Ok(index)
+    }
+
+    /// Load the cached index, fetching it if it is not cached yet
+    pub async fn load_index(&self, refresh: bool) -> Result<CyreneRegistryIndex, CyreneError> {
+        if refresh || !fs::exists(&self.index_path).map_err(CyreneError::RegistryRead)? {
+            return self.update_index().await;
+        }
+        let index_read = fs::read_to_string(&self.index_path).map_err(CyreneError::RegistryRead)?;
+        toml::de::from_str(&index_read).map_err(CyreneError::RegistryDeserialize)
+    }
+
+    /// Fetch the recipe of a plugin listed in the registry
+    pub async fn fetch_plugin(&self, name: &str, refresh: bool) -> Result<String, CyreneError> {
+        let index = self.load_index(refresh).await?;
+        let entry = index
+            .plugins
+            .get(name)
+            .ok_or(CyreneError::RegistryPluginNotFound(name.to_string()))?;
+
+        self.fetch(&entry.url).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let index: CyreneRegistryIndex = toml::de::from_str(
+            r#"
+[plugins.node]
+url = "https://example.com/node.cyrene"
+description = "JavaScript runtime"
+
+[plugins.deno]
+url = "https://example.com/deno.cyrene"
+description = "Secure JavaScript and TypeScript runtime"
+
+[plugins.go]
+url = "https://example.com/go.cyrene"
+"#,
+        )
+        .unwrap();
+
+        let names: Vec<String> = index
+            .search("javascript")
+            .into_iter()
+            .map(|f| f.0)
+            .collect();
+        assert_eq!(names, vec!["deno", "node"]);
+        assert_eq!(index.search("go").len(), 1);
+    }
+}
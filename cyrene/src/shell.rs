@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    /// POSIX shells such as bash and zsh
+    #[default]
+    Sh,
+    Fish,
+    Powershell,
+}
+
+/// Shell snippet that puts linked binaries on PATH
+pub fn path_snippet(shell: Shell, exe_dir: &Path) -> String {
+    let exe_dir = exe_dir.display();
+    match shell {
+        Shell::Sh => format!("export PATH=\"{}:$PATH\"", exe_dir),
+        Shell::Fish => format!("fish_add_path --global --prepend \"{}\"", exe_dir),
+        Shell::Powershell => format!(
+            "$env:PATH = \"{}\" + [IO.Path]::PathSeparator + $env:PATH",
+            exe_dir
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_path_snippet() {
+        let exe_dir = PathBuf::from("/home/user/.local/bin");
+
+        assert_eq!(
+            path_snippet(Shell::Sh, &exe_dir),
+            "export PATH=\"/home/user/.local/bin:$PATH\""
+        );
+        assert_eq!(
+            path_snippet(Shell::Fish, &exe_dir),
+            "fish_add_path --global --prepend \"/home/user/.local/bin\""
+        );
+    }
+}
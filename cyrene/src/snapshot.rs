@@ -0,0 +1,57 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::CyreneConfig, errors::CyreneError};
+
+/// Portable record of config, linked versions and installed versions
+#[derive(Serialize, Deserialize)]
+pub struct CyreneSnapshot {
+    pub config: CyreneConfig,
+    /// Versions in the current lockfile, which are the linked versions
+    pub versions: BTreeMap<String, String>,
+    /// All installed versions of each app
+    pub installed: BTreeMap<String, Vec<String>>,
+}
+
+impl CyreneSnapshot {
+    pub fn read(path: &Path) -> Result<Self, CyreneError> {
+        let snapshot_read = fs::read_to_string(path)
+            .map_err(|e| CyreneError::SnapshotRead(path.to_path_buf(), e))?;
+        toml::de::from_str(&snapshot_read).map_err(CyreneError::SnapshotDeserialize)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), CyreneError> {
+        let snapshot_write = toml::ser::to_string(self).map_err(CyreneError::SnapshotSerialize)?;
+        fs::write(path, snapshot_write)
+            .map_err(|e| CyreneError::SnapshotWrite(path.to_path_buf(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let snapshot = CyreneSnapshot {
+            config: CyreneConfig {
+                keep_on_upgrade: Some(1),
+                ..Default::default()
+            },
+            versions: BTreeMap::from([("node".to_string(), "22.0.0".to_string())]),
+            installed: BTreeMap::from([(
+                "node".to_string(),
+                vec!["22.0.0".to_string(), "20.0.0".to_string()],
+            )]),
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cyrene.snapshot");
+        snapshot.write(&path).unwrap();
+
+        let snapshot = CyreneSnapshot::read(&path).unwrap();
+        assert_eq!(snapshot.config.keep_on_upgrade, Some(1));
+        assert_eq!(snapshot.versions.get("node").unwrap(), "22.0.0");
+        assert_eq!(snapshot.installed.get("node").unwrap().len(), 2);
+    }
+}
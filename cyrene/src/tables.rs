@@ -1,3 +1,5 @@
+use std::{path::PathBuf, time::Duration};
+
 use tabled::{
     Table, Tabled,
     settings::{
@@ -7,7 +9,13 @@ use tabled::{
     },
 };
 
-use crate::{AppVersion, AppVersionAction, AppVersionUpgradeAction};
+use crate::{
+    AppVersion, AppVersionAction, AppVersionUpgradeAction,
+    app::AppPlanStep,
+    manager::{CyreneAppLink, CyreneBinaryConflict, CyreneLinkedBinary, CyreneTestLink},
+    output::resultln,
+    registry::CyreneRegistryEntry,
+};
 
 #[derive(Tabled)]
 #[tabled(rename_all = "Upper Title Case")]
@@ -29,6 +37,83 @@ pub struct CyreneAppVersionsAllRow {
     pub version: String,
     pub linked: bool,
 }
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneLockfileCheckRow {
+    pub name: String,
+    pub pinned_version: String,
+    pub available: String,
+}
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneBinaryProviderRow {
+    pub name: String,
+    pub version: String,
+    pub path: String,
+}
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneLinkedBinaryRow {
+    pub binary: String,
+    pub name: String,
+    pub version: String,
+    pub status: String,
+}
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneAppLinkRow {
+    pub binary: String,
+    pub name: String,
+    pub version: String,
+    pub target: String,
+}
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneBinaryConflictRow {
+    pub binary: String,
+    pub provided_by: String,
+    pub linked: String,
+}
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneTestLinkRow {
+    pub binary: String,
+    pub target: String,
+    pub created: bool,
+    pub target_exists: bool,
+}
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneAppPlanRow {
+    pub step: String,
+    pub details: String,
+    pub network: String,
+}
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneDiskUsageRow {
+    pub name: String,
+    pub size: String,
+}
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneDiskUsageVersionRow {
+    pub name: String,
+    pub version: String,
+    pub size: String,
+}
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneTimingRow {
+    pub name: String,
+    pub time: String,
+}
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct CyreneRegistrySearchRow {
+    pub name: String,
+    pub description: String,
+}
 
 impl From<&(String, String)> for CyreneAppVersionsRow {
     fn from(value: &(String, String)) -> Self {
@@ -77,6 +162,125 @@ impl From<&AppVersionUpgradeAction> for CyreneAppVersionsUpgradeRow {
     }
 }
 
+impl From<&(String, String, bool)> for CyreneLockfileCheckRow {
+    fn from(value: &(String, String, bool)) -> Self {
+        CyreneLockfileCheckRow {
+            name: value.0.clone(),
+            pinned_version: value.1.clone(),
+            available: if value.2 { "yes" } else { "no" }.to_string(),
+        }
+    }
+}
+
+impl From<&(String, String, PathBuf)> for CyreneBinaryProviderRow {
+    fn from(value: &(String, String, PathBuf)) -> Self {
+        CyreneBinaryProviderRow {
+            name: value.0.clone(),
+            version: value.1.clone(),
+            path: value.2.to_string_lossy().to_string(),
+        }
+    }
+}
+
+impl From<&CyreneAppLink> for CyreneAppLinkRow {
+    fn from(value: &CyreneAppLink) -> Self {
+        CyreneAppLinkRow {
+            binary: value.binary.clone(),
+            name: value.app.clone(),
+            version: value.version.clone(),
+            target: value.target.to_string_lossy().to_string(),
+        }
+    }
+}
+
+impl From<&CyreneLinkedBinary> for CyreneLinkedBinaryRow {
+    fn from(value: &CyreneLinkedBinary) -> Self {
+        CyreneLinkedBinaryRow {
+            binary: value.binary.clone(),
+            name: value.app.clone(),
+            version: value.version.clone(),
+            status: if value.valid {
+                "ok".to_string()
+            } else {
+                format!("dangling ({})", value.target.to_string_lossy())
+            },
+        }
+    }
+}
+
+impl From<&CyreneBinaryConflict> for CyreneBinaryConflictRow {
+    fn from(value: &CyreneBinaryConflict) -> Self {
+        CyreneBinaryConflictRow {
+            binary: value.binary.clone(),
+            provided_by: value
+                .providers
+                .iter()
+                .map(|(name, version)| format!("{} {}", name, version))
+                .collect::<Vec<_>>()
+                .join(", "),
+            linked: value.linked.clone().unwrap_or_else(|| "-".to_string()),
+        }
+    }
+}
+
+impl From<&CyreneTestLink> for CyreneTestLinkRow {
+    fn from(value: &CyreneTestLink) -> Self {
+        CyreneTestLinkRow {
+            binary: value.binary.clone(),
+            target: value.target.to_string_lossy().to_string(),
+            created: value.created,
+            target_exists: value.target_exists,
+        }
+    }
+}
+
+impl From<&AppPlanStep> for CyreneAppPlanRow {
+    fn from(value: &AppPlanStep) -> Self {
+        CyreneAppPlanRow {
+            step: value.step.clone(),
+            details: value.detail.clone(),
+            network: if value.network { "yes" } else { "no" }.to_string(),
+        }
+    }
+}
+
+impl From<&(String, u64)> for CyreneDiskUsageRow {
+    fn from(value: &(String, u64)) -> Self {
+        CyreneDiskUsageRow {
+            name: value.0.clone(),
+            size: format_size(value.1),
+        }
+    }
+}
+
+impl From<&(String, String, u64)> for CyreneDiskUsageVersionRow {
+    fn from(value: &(String, String, u64)) -> Self {
+        CyreneDiskUsageVersionRow {
+            name: value.0.clone(),
+            version: value.1.clone(),
+            size: format_size(value.2),
+        }
+    }
+}
+
+impl From<&(String, Duration)> for CyreneTimingRow {
+    fn from(value: &(String, Duration)) -> Self {
+        CyreneTimingRow {
+            name: value.0.clone(),
+            time: format!("{:.2}s", value.1.as_secs_f64()),
+        }
+    }
+}
+
+impl From<&(String, CyreneRegistryEntry)> for CyreneRegistrySearchRow {
+    fn from(value: &(String, CyreneRegistryEntry)) -> Self {
+        CyreneRegistrySearchRow {
+            name: value.0.clone(),
+            description: value.1.description.clone().unwrap_or_default(),
+        }
+    }
+}
+
 pub fn cyrene_app_versions(versions: &[(String, String)], long_ver: bool) {
     if long_ver {
         let table_items = versions.iter().map(CyreneAppVersionsRow::from);
@@ -90,9 +294,40 @@ pub fn cyrene_app_versions(versions: &[(String, String)], long_ver: bool) {
         ));
         table.modify(Columns::first(), Alignment::left());
 
-        println!("{}", table);
+        resultln!("{}", table);
     } else {
-        versions.iter().for_each(|f| println!("{}", f.1));
+        versions.iter().for_each(|f| resultln!("{}", f.1));
+    }
+}
+
+pub fn cyrene_app_versions_grouped(name: &str, versions: &[(String, String)], long_ver: bool) {
+    if long_ver {
+        let table_items = versions.iter().map(CyreneAppVersionsRow::from);
+
+        let theme = Style::modern();
+        let mut table = Table::new(table_items);
+        table.with(theme);
+        table.with(Panel::header(name));
+        table.with(BorderCorrection::span());
+        table.with(Colorization::exact(
+            [Color::rgb_fg(255, 175, 255)],
+            Rows::one(1),
+        ));
+        table.modify(Columns::first(), Alignment::left());
+        table.modify(Columns::first(), Width::increase(25));
+
+        resultln!("{}", table);
+    } else {
+        resultln!("{}:", name);
+        versions.iter().for_each(|f| resultln!("  {}", f.1));
+    }
+}
+
+pub fn cyrene_app_versions_latest(versions: &[(String, String)], long_ver: bool) {
+    if long_ver {
+        cyrene_app_versions(versions, long_ver);
+    } else {
+        versions.iter().for_each(|f| resultln!("{}: {}", f.0, f.1));
     }
 }
 
@@ -109,10 +344,10 @@ pub fn cyrene_app_versions_all(versions: &[CyreneAppVersionsAllRow], long_ver: b
         ));
         table.modify(Columns::first(), Alignment::left());
 
-        println!("{}", table);
+        resultln!("{}", table);
     } else {
         versions.iter().for_each(|f| {
-            println!(
+            resultln!(
                 "{}: {} {}",
                 f.name,
                 f.version,
@@ -122,6 +357,21 @@ pub fn cyrene_app_versions_all(versions: &[CyreneAppVersionsAllRow], long_ver: b
     }
 }
 
+/// Print installed versions as aligned columns without borders, for `grep` and `awk`
+pub fn cyrene_app_versions_flat(versions: &[CyreneAppVersionsAllRow]) {
+    let name_width = versions.iter().map(|f| f.name.len()).max().unwrap_or(0);
+    let version_width = versions.iter().map(|f| f.version.len()).max().unwrap_or(0);
+    for f in versions {
+        let row = format!(
+            "{:name_width$} {:version_width$} {}",
+            f.name,
+            f.version,
+            if f.linked { "*" } else { "" },
+        );
+        resultln!("{}", row.trim_end());
+    }
+}
+
 pub fn cyrene_app_install(versions: &[AppVersionAction]) {
     let table_items = versions.iter().map(CyreneAppVersionsRow::from);
 
@@ -141,7 +391,51 @@ pub fn cyrene_app_install(versions: &[AppVersionAction]) {
     table.modify(Columns::first(), Alignment::left());
     table.modify(Columns::first(), Width::increase(25));
 
-    println!("{}", table);
+    resultln!("{}", table);
+}
+
+pub fn cyrene_app_relink(versions: &[AppVersionAction]) {
+    let table_items = versions.iter().map(CyreneAppVersionsRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Apps to be relinked"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::FG_BRIGHT_GREEN],
+        Columns::last(),
+    ));
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+    table.modify(Columns::first(), Width::increase(25));
+
+    resultln!("{}", table);
+}
+
+pub fn cyrene_app_reinstall(versions: &[AppVersionAction]) {
+    let table_items = versions.iter().map(CyreneAppVersionsRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Apps to be reinstalled"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::FG_BRIGHT_GREEN],
+        Columns::last(),
+    ));
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+    table.modify(Columns::first(), Width::increase(25));
+
+    resultln!("{}", table);
 }
 
 pub fn cyrene_app_upgrade(versions: &[AppVersionUpgradeAction]) {
@@ -167,7 +461,7 @@ pub fn cyrene_app_upgrade(versions: &[AppVersionUpgradeAction]) {
     table.modify(Columns::first(), Alignment::left());
     table.modify(Columns::first(), Width::increase(25));
 
-    println!("{}", table);
+    resultln!("{}", table);
 }
 
 pub fn cyrene_app_remove(versions: &[AppVersion]) {
@@ -185,7 +479,29 @@ pub fn cyrene_app_remove(versions: &[AppVersion]) {
     ));
     table.modify(Columns::first(), Alignment::left());
     table.modify(Columns::first(), Width::increase(25));
-    println!("{}", table);
+    resultln!("{}", table);
+}
+
+pub fn cyrene_app_link(versions: &[AppVersionAction]) {
+    let table_items = versions.iter().map(CyreneAppVersionsRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Installed apps to be linked"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::FG_BRIGHT_GREEN],
+        Columns::last(),
+    ));
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+    table.modify(Columns::first(), Width::increase(25));
+
+    resultln!("{}", table);
 }
 
 pub fn cyrene_app_install_unneeded(versions: &[AppVersionAction]) {
@@ -207,7 +523,7 @@ pub fn cyrene_app_install_unneeded(versions: &[AppVersionAction]) {
     table.modify(Columns::first(), Alignment::left());
     table.modify(Columns::first(), Width::increase(25));
 
-    println!("{}", table);
+    resultln!("{}", table);
 }
 
 pub fn cyrene_app_upgrade_unneeded(versions: &[AppVersionUpgradeAction]) {
@@ -229,5 +545,212 @@ pub fn cyrene_app_upgrade_unneeded(versions: &[AppVersionUpgradeAction]) {
     table.modify(Columns::first(), Alignment::left());
     table.modify(Columns::first(), Width::increase(25));
 
-    println!("{}", table);
+    resultln!("{}", table);
+}
+
+pub fn cyrene_lockfile_check(versions: &[(String, String, bool)]) {
+    let table_items = versions.iter().map(CyreneLockfileCheckRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Lockfile versions"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+    table.modify(Columns::first(), Width::increase(25));
+
+    resultln!("{}", table);
+}
+
+pub fn cyrene_binary_providers(binary: &str, providers: &[(String, String, PathBuf)]) {
+    let table_items = providers.iter().map(CyreneBinaryProviderRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header(format!("Apps providing {}", binary)));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+
+    resultln!("{}", table);
+}
+
+pub fn cyrene_linked_binaries(linked: &[CyreneLinkedBinary]) {
+    let table_items = linked.iter().map(CyreneLinkedBinaryRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Linked binaries"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+
+    resultln!("{}", table);
+}
+
+pub fn cyrene_app_links(links: &[CyreneAppLink]) {
+    let table_items = links.iter().map(CyreneAppLinkRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Binaries of linked apps"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+
+    resultln!("{}", table);
+}
+
+pub fn cyrene_binary_conflicts(conflicts: &[CyreneBinaryConflict]) {
+    let table_items = conflicts.iter().map(CyreneBinaryConflictRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Conflicting binaries"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+
+    resultln!("{}", table);
+}
+
+pub fn cyrene_test_links(links: &[CyreneTestLink]) {
+    let table_items = links.iter().map(CyreneTestLinkRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Test links"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+
+    resultln!("{}", table);
+}
+
+pub fn cyrene_app_plan(name: &str, version: &str, steps: &[AppPlanStep]) {
+    let table_items = steps.iter().map(CyreneAppPlanRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header(format!("Installing {} {}", name, version)));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+
+    resultln!("{}", table);
+}
+
+/// Format a size in bytes with binary units
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+pub fn cyrene_disk_usage(usage: &[(String, u64)]) {
+    let table_items = usage.iter().map(CyreneDiskUsageRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Disk usage"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+    table.modify(Columns::last(), Alignment::right());
+
+    resultln!("{}", table);
+}
+
+pub fn cyrene_disk_usage_by_version(usage: &[(String, String, u64)]) {
+    let table_items = usage.iter().map(CyreneDiskUsageVersionRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Disk usage"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+    table.modify(Columns::last(), Alignment::right());
+
+    resultln!("{}", table);
+}
+
+pub fn cyrene_timings(timings: &[(String, Duration)]) {
+    let table_items = timings.iter().map(CyreneTimingRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header("Time spent installing and linking"));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+    table.modify(Columns::last(), Alignment::right());
+
+    resultln!("{}", table);
+}
+
+pub fn cyrene_registry_search(query: &str, plugins: &[(String, CyreneRegistryEntry)]) {
+    let table_items = plugins.iter().map(CyreneRegistrySearchRow::from);
+
+    let theme = Style::modern();
+    let mut table = Table::new(table_items);
+    table.with(theme);
+    table.with(Panel::header(format!("Plugins matching {}", query)));
+    table.with(BorderCorrection::span());
+    table.with(Colorization::exact(
+        [Color::rgb_fg(255, 175, 255)],
+        Rows::one(1),
+    ));
+    table.modify(Columns::first(), Alignment::left());
+
+    resultln!("{}", table);
 }
@@ -1,14 +1,28 @@
-use std::sync::Arc;
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use console::{Color, style};
 use log::debug;
 
-use crate::{errors::CyreneError, manager::CyreneManager};
+use crate::{
+    app_module::sources::Cancellation,
+    errors::CyreneError,
+    manager::CyreneManager,
+    output::{self, CyreneEvent, outputln},
+    tables,
+};
 
 #[derive(Debug)]
 struct AppActionCommand {
     app: String,
     version: String,
+    local_path: Option<PathBuf>,
+    /// Remove the existing installation first
+    reinstall: bool,
 }
 #[derive(Debug)]
 enum AppRemoveActionCommand {
@@ -21,6 +35,10 @@ enum AppFinishActionCommand {
         app: String,
         version: Option<String>,
     },
+    LockfileRange {
+        app: String,
+        range: Option<String>,
+    },
     Link {
         app: String,
         version: String,
@@ -35,6 +53,15 @@ pub enum TransactionCommands {
         app: String,
         version: String,
     },
+    InstallLocal {
+        app: String,
+        version: String,
+        path: PathBuf,
+    },
+    Reinstall {
+        app: String,
+        version: String,
+    },
     Remove {
         app: String,
         version: String,
@@ -46,6 +73,11 @@ pub enum TransactionCommands {
         app: String,
         version: Option<String>,
     },
+    /// Record the version requirement an app was installed with
+    LockfileRange {
+        app: String,
+        range: Option<String>,
+    },
     Link {
         app: String,
         version: String,
@@ -58,14 +90,14 @@ pub enum TransactionCommands {
 
 pub struct TransactionExecutor {
     manager: Arc<CyreneManager>,
-    // Installation commands are run first
+    // Installation commands, with their post installs, are run first
     install: Vec<AppActionCommand>,
-    // Then post installs
-    post_install: Vec<AppActionCommand>,
     // Then removes (for upgrading)
     remove: Vec<AppRemoveActionCommand>,
     // Update lockfiles here
     finish: Vec<AppFinishActionCommand>,
+    // Print time spent installing and linking each app
+    timings: bool,
 }
 
 impl TransactionExecutor {
@@ -73,20 +105,42 @@ impl TransactionExecutor {
         Self {
             manager,
             install: Vec::new(),
-            post_install: Vec::new(),
             remove: Vec::new(),
             finish: Vec::new(),
+            timings: false,
         }
     }
 
+    pub fn with_timings(mut self, timings: bool) -> Self {
+        self.timings = timings;
+        self
+    }
+
     pub fn add(&mut self, cmd: TransactionCommands) {
         match cmd {
             TransactionCommands::Install { app, version } => {
                 self.install.push(AppActionCommand {
-                    app: app.clone(),
-                    version: version.clone(),
+                    app,
+                    version,
+                    local_path: None,
+                    reinstall: false,
+                });
+            }
+            TransactionCommands::InstallLocal { app, version, path } => {
+                self.install.push(AppActionCommand {
+                    app,
+                    version,
+                    local_path: Some(path),
+                    reinstall: false,
+                });
+            }
+            TransactionCommands::Reinstall { app, version } => {
+                self.install.push(AppActionCommand {
+                    app,
+                    version,
+                    local_path: None,
+                    reinstall: true,
                 });
-                self.post_install.push(AppActionCommand { app, version });
             }
             TransactionCommands::Remove { app, version } => {
                 self.remove
@@ -98,6 +152,9 @@ impl TransactionExecutor {
             TransactionCommands::LockfileUpdate { app, version } => self
                 .finish
                 .push(AppFinishActionCommand::LockfileUpdate { app, version }),
+            TransactionCommands::LockfileRange { app, range } => self
+                .finish
+                .push(AppFinishActionCommand::LockfileRange { app, range }),
             TransactionCommands::Link {
                 app,
                 version,
@@ -115,45 +172,87 @@ impl TransactionExecutor {
 
     pub async fn execute(&self) -> Result<bool, CyreneError> {
         debug!("Install commands: {:?}", self.install.iter());
-        debug!("Post-install commands: {:?}", self.post_install.iter());
         debug!("Finish commands: {:?}", self.finish.iter());
         debug!("Remove commands: {:?}", self.remove.iter());
+        let mut timings: BTreeMap<String, Duration> = BTreeMap::new();
         let install = self.install.iter();
         for install in install {
-            println!(
+            let started = Instant::now();
+            if install.reinstall {
+                outputln!(
+                    "Removing broken installation of {} version {}",
+                    style(&install.app).fg(Color::Color256(219)).bold(),
+                    style(&install.version).fg(Color::Yellow).bold(),
+                );
+                self.manager
+                    .uninstall_version(&install.app, &install.version)?;
+            }
+            outputln!(
                 "Installing {} version {}",
                 style(&install.app).fg(Color::Color256(219)).bold(),
                 style(&install.version).fg(Color::Green).bold(),
             );
+            output::emit(CyreneEvent::InstallStarted {
+                app: &install.app,
+                version: &install.version,
+            });
+            // `install_timeout` covers both the install and the post-install of an app
+            let cancel = Cancellation::default();
+            let install_app = async {
+                match &install.local_path {
+                    Some(local_path) => {
+                        self.manager
+                            .install_version_from_local(
+                                &install.app,
+                                &install.version,
+                                local_path,
+                                &cancel,
+                            )
+                            .await?
+                    }
+                    None => {
+                        self.manager
+                            .install_version(&install.app, &install.version, &cancel)
+                            .await?
+                    }
+                }
+                outputln!(
+                    "Executing post install commands for {} version {}",
+                    style(&install.app).fg(Color::Color256(219)).bold(),
+                    style(&install.version).fg(Color::Green).bold(),
+                );
+                output::emit(CyreneEvent::PostInstallStarted {
+                    app: &install.app,
+                    version: &install.version,
+                });
+                self.manager
+                    .post_install_version(&install.app, &install.version)
+                    .await
+            };
             self.manager
-                .install_version(&install.app, &install.version)
-                .await?;
-        }
-        let post_install = self.post_install.iter();
-        for post_install in post_install {
-            println!(
-                "Executing post install commands for {} version {}",
-                style(&post_install.app).fg(Color::Color256(219)).bold(),
-                style(&post_install.version).fg(Color::Green).bold(),
-            );
-            self.manager
-                .post_install_version(&post_install.app, &post_install.version)
+                .with_install_timeout(&install.app, &install.version, &cancel, install_app)
                 .await?;
+            *timings.entry(install.app.clone()).or_default() += started.elapsed();
         }
         let remove = self.remove.iter();
         for remove in remove {
             match remove {
                 AppRemoveActionCommand::Remove { app, version } => {
-                    println!(
+                    outputln!(
                         "Removing {} version {}",
                         style(&app).fg(Color::Color256(219)).bold(),
                         style(&version).fg(Color::Green).bold(),
                     );
                     self.manager.uninstall_version(app, version)?;
+                    output::emit(CyreneEvent::Removed {
+                        app,
+                        version: Some(version),
+                    });
                 }
                 AppRemoveActionCommand::RemoveAll { app } => {
-                    println!("Removing {}", style(&app).fg(Color::Color256(219)).bold(),);
+                    outputln!("Removing {}", style(&app).fg(Color::Color256(219)).bold(),);
                     self.manager.uninstall_all(app)?;
+                    output::emit(CyreneEvent::Removed { app, version: None });
                 }
             }
         }
@@ -162,34 +261,59 @@ impl TransactionExecutor {
             match finish {
                 AppFinishActionCommand::LockfileUpdate { app, version } => {
                     let version_string = version.clone().unwrap_or("".to_string());
-                    println!(
+                    outputln!(
                         "Updating lockfile for {} version {}",
                         style(&app).fg(Color::Color256(219)).bold(),
                         style(&version_string).fg(Color::Green).bold(),
                     );
                     self.manager.update_lockfile(app, version.as_deref())?;
+                    output::emit(CyreneEvent::LockfileUpdated {
+                        app,
+                        version: version.as_deref(),
+                    });
+                }
+                AppFinishActionCommand::LockfileRange { app, range } => {
+                    if let Some(range) = range {
+                        outputln!(
+                            "Keeping {} within {}",
+                            style(&app).fg(Color::Color256(219)).bold(),
+                            style(&range).fg(Color::Green).bold(),
+                        );
+                    }
+                    self.manager.update_lockfile_range(app, range.as_deref())?;
                 }
                 AppFinishActionCommand::Link {
                     app,
                     version,
                     overwrite,
                 } => {
-                    println!(
+                    outputln!(
                         "Linking binaries for {} version {}",
                         style(&app).fg(Color::Color256(219)).bold(),
                         style(&version).fg(Color::Green).bold(),
                     );
+                    let started = Instant::now();
                     self.manager.link_binaries(app, version, *overwrite)?;
+                    *timings.entry(app.clone()).or_default() += started.elapsed();
+                    output::emit(CyreneEvent::Linked { app, version });
                 }
                 AppFinishActionCommand::Unlink { app } => {
-                    println!(
+                    outputln!(
                         "Unlinking binaries for {}",
                         style(&app).fg(Color::Color256(219)).bold()
                     );
                     self.manager.unlink_binaries(app)?;
+                    output::emit(CyreneEvent::Unlinked { app });
                 }
             }
         }
+        debug!("Timings: {:?}", timings);
+        if self.timings && !timings.is_empty() {
+            let mut timings: Vec<(String, Duration)> = timings.into_iter().collect();
+            timings.sort_by_key(|f| std::cmp::Reverse(f.1));
+            outputln!();
+            tables::cyrene_timings(&timings);
+        }
         Ok(true)
     }
 }
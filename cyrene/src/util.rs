@@ -19,12 +19,38 @@ pub fn is_major_version_equal(old_version: &str, new_version: &str) -> Result<bo
     }
 }
 
+/// Strip the `=` and `v` prefixes commonly typed in front of a version, e.g. `v20.1.0`.
+///
+/// Range operators such as `^`, `~` and `>=` are kept. `=` is only stripped in front of a full
+/// version, as `=20.1` matches `20.1.x` while `20.1` matches any `20.x` from `20.1.0`.
+pub fn normalize_version(version: &str) -> &str {
+    let version = version.trim();
+    fn strip_v(version: &str) -> &str {
+        match version.strip_prefix(['v', 'V']) {
+            Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => rest,
+            _ => version,
+        }
+    }
+    match version.strip_prefix('=') {
+        Some(rest) => {
+            let rest = strip_v(rest.trim_start());
+            if Version::parse(rest).is_ok() {
+                rest
+            } else {
+                version
+            }
+        }
+        None => strip_v(version),
+    }
+}
+
 pub fn search_in_version(
     semver: bool,
     versions: Vec<String>,
     version_range: &str,
 ) -> Option<String> {
     if semver {
+        let version_range = normalize_version(version_range);
         let versionings: Vec<Version> = versions
             .iter()
             .map(|f| Version::parse(f))
@@ -43,3 +69,75 @@ pub fn search_in_version(
     }
     None
 }
+
+/// Match `text` against a pattern where `*` matches any sequence of characters
+pub fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = text.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_version() {
+        assert_eq!(normalize_version("v20.1.0"), "20.1.0");
+        assert_eq!(normalize_version("V20"), "20");
+        assert_eq!(normalize_version("=20.1.0"), "20.1.0");
+        assert_eq!(normalize_version("=v20.1.0"), "20.1.0");
+        assert_eq!(normalize_version("=20.1"), "=20.1");
+        assert_eq!(normalize_version("20.1.0"), "20.1.0");
+        assert_eq!(normalize_version("^20.1"), "^20.1");
+        assert_eq!(normalize_version("~20.1"), "~20.1");
+        assert_eq!(normalize_version(">=20.1"), ">=20.1");
+        assert_eq!(normalize_version("vnext"), "vnext");
+        assert_eq!(normalize_version(" v20.1.0 "), "20.1.0");
+    }
+
+    #[test]
+    fn test_search_in_version_prefixes() {
+        let versions = vec!["22.0.0".to_string(), "20.1.0".to_string()];
+        for version in ["v20.1.0", "=20.1.0", "v20", "^20"] {
+            assert_eq!(
+                search_in_version(true, versions.clone(), version),
+                Some("20.1.0".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_in_version_partial_exact() {
+        let versions = vec!["20.2.0".to_string(), "20.1.3".to_string()];
+        assert_eq!(
+            search_in_version(true, versions, "=20.1"),
+            Some("20.1.3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("*-beta.*", "2.0.0-beta.1"));
+        assert!(!matches_pattern("*-beta.*", "2.0.0"));
+        assert!(matches_pattern("nightly-*", "nightly-2025-01-01"));
+        assert!(matches_pattern("1.*.0", "1.2.0"));
+        assert!(!matches_pattern("1.*.0", "1.2.1"));
+        assert!(matches_pattern("stable", "stable"));
+        assert!(!matches_pattern("stable", "stable-1"));
+        assert!(matches_pattern("*", "anything"));
+    }
+}
@@ -28,4 +28,4 @@ impl CyreneVersion {
             std::cmp::Ordering::Equal
         }
     }
-}
\ No newline at end of file
+}
@@ -4,6 +4,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use console::{Color, style};
 use serde::{Deserialize, Serialize};
 
 use crate::errors::CyreneError;
@@ -23,26 +24,53 @@ impl CyreneVersionCacheManager {
         }
     }
 
-    pub fn get_versions(&self, name: &str) -> Result<Vec<String>, CyreneError> {
-        let cache: CyreneVersionsCache = if !fs::exists(&self.cache_path)
-            .map_err(CyreneError::VersionCacheRead)?
-        {
-            let new_cache = CyreneVersionsCache {
+    fn load_cache(&self) -> Result<CyreneVersionsCache, CyreneError> {
+        if !fs::exists(&self.cache_path).map_err(CyreneError::VersionCacheRead)? {
+            return Ok(CyreneVersionsCache {
                 versions: BTreeMap::new(),
-            };
-            let new_cache_file =
-                toml::ser::to_string(&new_cache).map_err(CyreneError::VersionCacheSerialize)?;
-            fs::write(&self.cache_path, new_cache_file).map_err(CyreneError::VersionCacheWrite)?;
+            });
+        }
+        let file = fs::read_to_string(&self.cache_path).map_err(CyreneError::VersionCacheRead)?;
+        match toml::de::from_str(&file) {
+            Ok(cache) => Ok(cache),
+            Err(e) => {
+                // Back up the corrupt cache and start over
+                let mut backup_path = self.cache_path.clone().into_os_string();
+                backup_path.push(".bak");
+                eprintln!(
+                    "{} version cache is corrupt, backing up to {}: {}",
+                    style("Warning:").fg(Color::Yellow).bold(),
+                    PathBuf::from(&backup_path).display(),
+                    e
+                );
+                fs::rename(&self.cache_path, &backup_path)
+                    .map_err(CyreneError::VersionCacheWrite)?;
 
-            new_cache
-        } else {
-            let file =
-                fs::read_to_string(&self.cache_path).map_err(CyreneError::VersionCacheRead)?;
-            let cache: CyreneVersionsCache =
-                toml::de::from_str(&file).map_err(CyreneError::VersionCacheDeserialize)?;
+                Ok(CyreneVersionsCache {
+                    versions: BTreeMap::new(),
+                })
+            }
+        }
+    }
 
-            cache
-        };
+    pub fn cache_path(&self) -> &Path {
+        &self.cache_path
+    }
+
+    /// Error parsing the cache, if it is corrupt
+    pub fn check_cache(&self) -> Result<Option<String>, CyreneError> {
+        if !fs::exists(&self.cache_path).map_err(CyreneError::VersionCacheRead)? {
+            return Ok(None);
+        }
+        let file = fs::read_to_string(&self.cache_path).map_err(CyreneError::VersionCacheRead)?;
+
+        Ok(toml::de::from_str::<CyreneVersionsCache>(&file)
+            .err()
+            .map(|e| e.to_string()))
+    }
+
+    pub fn get_versions(&self, name: &str) -> Result<Vec<String>, CyreneError> {
+        let cache = self.load_cache()?;
 
         match cache.versions.get(name) {
             Some(some) => Ok(some.clone()),
@@ -54,16 +82,7 @@ impl CyreneVersionCacheManager {
         name: &str,
         versions: Vec<String>,
     ) -> Result<(), CyreneError> {
-        let mut cache: CyreneVersionsCache =
-            if !fs::exists(&self.cache_path).map_err(CyreneError::VersionCacheRead)? {
-                CyreneVersionsCache {
-                    versions: BTreeMap::new(),
-                }
-            } else {
-                let file =
-                    fs::read_to_string(&self.cache_path).map_err(CyreneError::VersionCacheRead)?;
-                toml::de::from_str(&file).map_err(CyreneError::VersionCacheDeserialize)?
-            };
+        let mut cache = self.load_cache()?;
         cache.versions.insert(String::from(name), versions);
         let cache_file =
             toml::ser::to_string(&cache).map_err(CyreneError::VersionCacheSerialize)?;
@@ -71,4 +90,50 @@ impl CyreneVersionCacheManager {
 
         Ok(())
     }
+
+    /// Drop cached versions of apps that `keep` rejects, returning their names
+    pub fn prune(&self, keep: impl Fn(&str) -> bool) -> Result<Vec<String>, CyreneError> {
+        let mut cache = self.load_cache()?;
+        let mut removed = Vec::new();
+        cache.versions.retain(|name, _| {
+            if keep(name) {
+                return true;
+            }
+            removed.push(name.clone());
+            false
+        });
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+        let cache_file =
+            toml::ser::to_string(&cache).map_err(CyreneError::VersionCacheSerialize)?;
+        fs::write(&self.cache_path, cache_file).map_err(CyreneError::VersionCacheWrite)?;
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = CyreneVersionCacheManager::new(&dir.path().join("versions.toml"));
+        manager
+            .update_version_cache("node", vec!["22.0.0".to_string()])
+            .unwrap();
+        manager
+            .update_version_cache("removed", vec!["1.0.0".to_string()])
+            .unwrap();
+
+        assert_eq!(
+            manager.prune(|name| name.eq("node")).unwrap(),
+            vec!["removed"]
+        );
+        assert_eq!(manager.get_versions("node").unwrap(), vec!["22.0.0"]);
+        assert!(manager.get_versions("removed").unwrap().is_empty());
+        assert!(manager.prune(|name| name.eq("node")).unwrap().is_empty());
+    }
 }